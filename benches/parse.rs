@@ -0,0 +1,60 @@
+// Baseline from `RUST_BACKTRACE=0 cargo bench` on the machine this was added on:
+//
+//   chunk_try_from/16        ~130 ns
+//   chunk_try_from/1024      ~3.0 µs
+//   chunk_try_from/65536     ~186 µs
+//   png_try_from/10          ~3.4 µs
+//   png_try_from/100         ~34 µs
+//   png_try_from/1000        ~315 µs
+//
+// png_try_from should grow linearly with the chunk count; a jump well past 10x between
+// neighbouring sizes points at something quadratic in the multi-chunk parse. With
+// RUST_BACKTRACE=1 the small cases are around 100x slower, because the `ok_or(anyhow!(..))`
+// calls in Chunk::try_from build their error, backtrace included, even on success.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::png::Png;
+
+fn chunk_of_size(chunk_type: ChunkType, size: usize) -> Chunk {
+    Chunk::new(chunk_type, (0..size).map(|i| i as u8).collect())
+}
+
+// a header, `chunks` small message chunks and an end marker
+fn synthetic_png(chunks: usize) -> Vec<u8> {
+    let message = ChunkType::try_from(*b"ruSt").unwrap();
+    let mut all = vec![chunk_of_size(ChunkType::IHDR, 13)];
+    all.extend((0..chunks).map(|_| chunk_of_size(message.clone(), 64)));
+    all.push(chunk_of_size(ChunkType::IEND, 0));
+    Png::from_chunks(all).as_bytes()
+}
+
+fn bench_chunk(c: &mut Criterion) {
+    let mut group = c.benchmark_group("chunk_try_from");
+    for size in [16, 1024, 65536] {
+        let bytes = chunk_of_size(ChunkType::try_from(*b"ruSt").unwrap(), size).as_bytes();
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &bytes, |b, bytes| {
+            b.iter(|| Chunk::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_png(c: &mut Criterion) {
+    let mut group = c.benchmark_group("png_try_from");
+    for chunks in [10, 100, 1000] {
+        let bytes = synthetic_png(chunks);
+        group.throughput(Throughput::Elements(chunks as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(chunks), &bytes, |b, bytes| {
+            b.iter(|| Png::try_from(black_box(bytes.as_slice())).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_chunk, bench_png);
+criterion_main!(benches);
@@ -0,0 +1,253 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::{Args, ValueEnum};
+use serde::Deserialize;
+
+use crate::chunk_type::ChunkType;
+use crate::envelope::{self, Transforms};
+
+/// Options shared by every command that rewrites a file
+#[derive(Args, Debug, Default, Clone)]
+pub struct WriteArgs {
+    /// Copy the file about to be overwritten to <FILE><SUFFIX> first
+    #[arg(long, value_name = "SUFFIX", num_args = 0..=1, require_equals = true, default_missing_value = ".bak")]
+    pub backup: Option<String>,
+    /// Overwrite an existing backup or output file
+    #[arg(long)]
+    pub force: bool,
+    /// Leave the file alone and write a sibling with SUFFIX before the extension, e.g. pic_out.png
+    #[arg(long, value_name = "SUFFIX", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    pub suffix: Option<String>,
+    /// Give the rewritten file the original's modification time
+    #[arg(long)]
+    pub preserve_times: bool,
+    /// Print one stable `status key=value ... file=PATH` line instead of a sentence, with
+    /// dry_run=true before file on a dry run
+    #[arg(long)]
+    pub porcelain: bool,
+    // set from the global --dry-run flag rather than per command
+    #[arg(skip)]
+    pub dry_run: bool,
+    // set from the global --json flag
+    #[arg(skip)]
+    pub format: OutputFormat,
+    // set from the global --quiet-success flag
+    #[arg(skip)]
+    pub quiet_success: bool,
+}
+
+impl WriteArgs {
+    // where a file that would be rewritten in place actually ends up
+    pub fn destination(&self, path: &Path) -> PathBuf {
+        let Some(suffix) = &self.suffix else {
+            return path.to_path_buf();
+        };
+        let mut name = path.file_stem().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        if let Some(extension) = path.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        path.with_file_name(name)
+    }
+}
+
+/// Options for how encode stores the message
+#[derive(Args, Debug, Default, Clone)]
+pub struct EncodeArgs {
+    /// Compress the message, decode detects this on its own
+    #[arg(long)]
+    pub compress: bool,
+    /// How hard --compress works, 0 stores the message and 9 is smallest but slowest
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        requires = "compress",
+        default_value_t = envelope::DEFAULT_LEVEL,
+        value_parser = clap::value_parser!(u32).range(0..=envelope::MAX_LEVEL as i64)
+    )]
+    pub deflate_level: u32,
+    /// Store the message base64 encoded, decode detects this on its own
+    #[arg(long)]
+    pub base64: bool,
+    /// Replace the first chunk of the type, if there is one, instead of adding another
+    #[arg(long)]
+    pub replace: bool,
+    /// Insert right after the first chunk of this type instead of before IEND
+    #[arg(long, value_name = "TYPE")]
+    pub after: Option<String>,
+    /// With --after, fall back to inserting before IEND when the type isn't there
+    #[arg(long, requires = "after")]
+    pub fallback: bool,
+    /// Split the message across chunks of at most SIZE bytes each, decode joins them back
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, conflicts_with_all = ["replace", "after"])]
+    pub fragment: Option<u64>,
+    /// Append an HMAC-SHA256 of the message keyed by KEY, decode --sign KEY checks it
+    #[arg(long, value_name = "KEY")]
+    pub sign: Option<String>,
+    /// Also copy every ancillary chunk of this PNG in, skipping ones already there. With
+    /// this the chunk type and message can be left out
+    #[arg(long, value_name = "PNG")]
+    pub append_file: Option<PathBuf>,
+}
+
+impl EncodeArgs {
+    pub fn key(&self) -> Option<&[u8]> {
+        self.sign.as_deref().map(str::as_bytes)
+    }
+
+    pub fn transforms(&self) -> Transforms {
+        Transforms {
+            compressed: self.compress,
+            base64: self.base64,
+            ..Transforms::default()
+        }
+    }
+}
+
+/// Options for what print shows about each chunk
+#[derive(Args, Debug, Default, Clone)]
+pub struct PrintArgs {
+    /// Show the property bits of each chunk's type
+    #[arg(long)]
+    pub flags: bool,
+    /// Show the byte offset each chunk starts at, counting the 8 byte signature
+    #[arg(long)]
+    pub offsets: bool,
+    /// Show sizes as exact byte counts instead of e.g. 1.4 MiB
+    #[arg(long)]
+    pub bytes: bool,
+    /// Only show chunks of these types, comma separated
+    #[arg(long, value_name = "TYPES", value_delimiter = ',', value_parser = ChunkType::from_str)]
+    pub types: Vec<ChunkType>,
+    /// Leave out chunks of these types, comma separated, applied after --types
+    #[arg(long, value_name = "TYPES", value_delimiter = ',', value_parser = ChunkType::from_str)]
+    pub exclude: Vec<ChunkType>,
+    // set from the global --color flag
+    #[arg(skip)]
+    pub color: bool,
+}
+
+impl PrintArgs {
+    pub fn is_filtered(&self) -> bool {
+        !self.types.is_empty() || !self.exclude.is_empty()
+    }
+
+    pub fn shows(&self, chunk_type: &ChunkType) -> bool {
+        (self.types.is_empty() || self.types.contains(chunk_type)) && !self.exclude.contains(chunk_type)
+    }
+}
+
+/// Options for which chunks decode reads and how it prints them
+#[derive(Args, Debug, Default, Clone)]
+pub struct DecodeArgs {
+    /// How to print the message
+    #[arg(long, value_enum, default_value_t)]
+    pub format: DecodeFormat,
+    /// Check the chunk's CRC and warn if it's wrong instead of refusing the file
+    #[arg(long)]
+    pub verify: bool,
+    /// Decode every chunk of the type, not just the first
+    #[arg(long)]
+    pub all: bool,
+    /// With --all, stop after this many chunks
+    #[arg(long, value_name = "N", requires = "all")]
+    pub count: Option<usize>,
+    /// Match chunk types whose letters equal CHUNK_TYPE in any case, e.g. rust finds RuSt
+    #[arg(long)]
+    pub ignore_case: bool,
+    /// Fail unless the message was signed with encode --sign KEY and is unchanged
+    #[arg(long, value_name = "KEY")]
+    pub sign: Option<String>,
+}
+
+impl DecodeArgs {
+    pub fn key(&self) -> Option<&[u8]> {
+        self.sign.as_deref().map(str::as_bytes)
+    }
+}
+
+/// How a command prints its results
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// How decode prints the message it finds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DecodeFormat {
+    /// The message as text, fails if it isn't valid UTF-8
+    #[default]
+    Utf8,
+    /// Lowercase hex digits, two per byte
+    Hex,
+    Base64,
+    /// The bytes exactly as stored, refused when stdout is a terminal
+    Raw,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color when writing to a terminal and NO_COLOR isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    // NO_COLOR counts when set to anything but the empty string, see no-color.org
+    pub fn enabled(self, terminal: bool, no_color: Option<&OsStr>) -> bool {
+        match self {
+            ColorChoice::Auto => terminal && no_color.is_none_or(OsStr::is_empty),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+// a byte count with an optional binary suffix, e.g. 4096, 512K or 256MiB
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let digits = size.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match size[digits.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        suffix => return Err(format!("unknown size suffix {suffix}, use K, M or G")),
+    };
+    let count: u64 = digits.parse().map_err(|_| format!("{size} is not a size"))?;
+    count
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("{size} is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination() {
+        let write = WriteArgs { suffix: Some("_out".to_string()), ..Default::default() };
+        assert_eq!(write.destination(Path::new("dir/pic.png")), Path::new("dir/pic_out.png"));
+        assert_eq!(write.destination(Path::new("pic.tar.png")), Path::new("pic.tar_out.png"));
+        assert_eq!(write.destination(Path::new("noext")), Path::new("noext_out"));
+        assert_eq!(WriteArgs::default().destination(Path::new("pic.png")), Path::new("pic.png"));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size("256MiB"), Ok(256 * 1024 * 1024));
+        assert_eq!(parse_size("1g"), Ok(1024 * 1024 * 1024));
+        assert!(parse_size("12T").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("-1").is_err());
+    }
+}
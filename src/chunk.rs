@@ -1,11 +1,40 @@
-use crc::{Crc, CRC_32_ISO_HDLC};
+use crc::{Crc, CRC_32_BZIP2, CRC_32_ISCSI, CRC_32_ISO_HDLC};
 use std::fmt::{Display, Formatter};
+use std::io::{self, Read, Write};
 use crate::chunk_type::ChunkType;
 use anyhow::{anyhow, bail, Context, Result};
 use thiserror::Error;
 
 static CRC_ALGO: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+static CRC_BZIP2: Crc<u32> = Crc::<u32>::new(&CRC_32_BZIP2);
+static CRC_CASTAGNOLI: Crc<u32> = Crc::<u32>::new(&CRC_32_ISCSI);
+
+// PNG only ever uses IsoHdlc, the other variants are for interop and fuzz testing
+// against tools that compute the checksum the wrong way
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CrcKind {
+    #[default]
+    IsoHdlc,
+    Bzip2,
+    Castagnoli,
+}
+
+impl CrcKind {
+    // covers the chunk type and data, never the length
+    pub fn checksum(self, chunk_type: &[u8; 4], data: &[u8]) -> u32 {
+        let algorithm = match self {
+            CrcKind::IsoHdlc => &CRC_ALGO,
+            CrcKind::Bzip2 => &CRC_BZIP2,
+            CrcKind::Castagnoli => &CRC_CASTAGNOLI,
+        };
+        let mut digest = algorithm.digest();
+        digest.update(chunk_type);
+        digest.update(data);
+        digest.finalize()
+    }
+}
 
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Chunk {
     length: u32,
     chunk_type: ChunkType,
@@ -14,9 +43,9 @@ pub struct Chunk {
 }
 
 #[derive(Error, Debug)]
-enum ChunkError {
-    #[error("Chunk length {0} is too large. It should not exceed 2^31 bytes")]
-    LengthTooLarge(u32),
+pub enum ChunkError {
+    #[error("Chunk length {0} is too large. It should not exceed 2^31 - 1 bytes")]
+    LengthTooLarge(usize),
     #[error("Chunk length {0} is incorrect (too large) for the chunk data")]
     IncorrectLength(u32),
     #[error("Crc mismatch. Expected: {expected}, Calculated: {calculated}")]
@@ -36,21 +65,25 @@ impl TryFrom<&[u8]> for Chunk {
                 .try_into()
                 .context("Failed to convert into integer from 4 bytes")?);
         
-        if 2u32.pow(31) < length {
-            bail!(ChunkError::LengthTooLarge(length));
+        if Self::MAX_LENGTH < length {
+            bail!(ChunkError::LengthTooLarge(length as usize));
         }
 
         let chunk_type_array: [u8; 4] = value.get(4..8)
             .ok_or(anyhow!("Input slice is too short, not of size 8 for chunk type"))?
             .try_into()?;
-        let chunk_type = ChunkType::try_from(chunk_type_array)?;
+        let chunk_type = ChunkType::from_bytes_relaxed(chunk_type_array)?;
         let chunk_data = value.get(8..8 + length as usize).ok_or(ChunkError::IncorrectLength(length))?;
         
-        let crc = u32::from_be_bytes(value[8 + length as usize..].try_into()
+        // only the 4 bytes right after the data, anything past them belongs to the next chunk
+        let crc_start = 8 + length as usize;
+        let crc = u32::from_be_bytes(value.get(crc_start..crc_start + 4)
+            .ok_or(anyhow!("Input slice is too short for chunk crc"))?
+            .try_into()
             .context("Failed to convert into integer from 4 bytes for crc")?);
 
         // returns an error if it occurs
-        Self::validate_crc(crc, chunk_data, &chunk_type.bytes())?;
+        Self::validate_crc(crc, chunk_data, &chunk_type.bytes(), CrcKind::default())?;
 
         Ok(Chunk {
             length,
@@ -61,22 +94,108 @@ impl TryFrom<&[u8]> for Chunk {
     }
 }
 
+impl TryFrom<&Vec<u8>> for Chunk {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Vec<u8>) -> Result<Self> {
+        Chunk::try_from(value.as_slice())
+    }
+}
+
+impl TryFrom<Vec<u8>> for Chunk {
+    type Error = anyhow::Error;
+
+    fn try_from(value: Vec<u8>) -> Result<Self> {
+        Chunk::try_from(value.as_slice())
+    }
+}
+
+// the serialized chunk, the same bytes as as_bytes
+impl From<&Chunk> for Vec<u8> {
+    fn from(chunk: &Chunk) -> Vec<u8> {
+        chunk.as_bytes()
+    }
+}
+
+impl From<Chunk> for Vec<u8> {
+    fn from(chunk: Chunk) -> Vec<u8> {
+        chunk.as_bytes()
+    }
+}
+
+// `{type, data, crc}` with the data base64 encoded. The length is implied by the data, and
+// a crc coming in has to match, leaving it out has it computed
+mod serde_impls {
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use serde::de::Error as _;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{Chunk, CrcKind};
+    use crate::chunk_type::ChunkType;
+
+    #[derive(Serialize)]
+    struct ChunkRef<'a> {
+        #[serde(rename = "type")]
+        chunk_type: &'a ChunkType,
+        data: String,
+        crc: u32,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct ChunkOwned {
+        #[serde(rename = "type")]
+        chunk_type: ChunkType,
+        data: String,
+        crc: Option<u32>,
+    }
+
+    impl Serialize for Chunk {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let data = BASE64.encode(&self.chunk_data);
+            ChunkRef { chunk_type: &self.chunk_type, data, crc: self.crc }.serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Chunk {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let owned = ChunkOwned::deserialize(deserializer)?;
+            let data = BASE64.decode(&owned.data).map_err(D::Error::custom)?;
+            let chunk = Chunk::try_new(owned.chunk_type, data).map_err(D::Error::custom)?;
+            if let Some(crc) = owned.crc {
+                let chunk_type = chunk.chunk_type.bytes();
+                Chunk::validate_crc(crc, &chunk.chunk_data, &chunk_type, CrcKind::default())
+                    .map_err(D::Error::custom)?;
+            }
+            Ok(chunk)
+        }
+    }
+}
+
 impl Display for Chunk {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Chunk length: {}", self.length)?;
         writeln!(f, "Chunk type: {}", self.chunk_type)?;
-        writeln!(f, "CRC: {}", self.crc)
+        writeln!(f, "CRC: {}", self.crc)?;
+        writeln!(f, "Data: {}", self.data_preview())
     }
 }
 
 impl Chunk {
+    const PREVIEW_LENGTH: usize = 32;
+
+    // largest data length a chunk is allowed to declare, the spec caps it at 2^31 - 1
+    pub const MAX_LENGTH: u32 = 2u32.pow(31) - 1;
+
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
-        let length = data.len() as u32;
+        Chunk::new_with_crc_kind(chunk_type, data, CrcKind::default())
+    }
 
-        let mut digest = CRC_ALGO.digest();
-        digest.update(&chunk_type.bytes()[..]);
-        digest.update(data.as_slice());
-        let crc = digest.finalize();
+    // anything but the default kind gives a chunk other PNG readers will reject
+    pub fn new_with_crc_kind(chunk_type: ChunkType, data: Vec<u8>, kind: CrcKind) -> Chunk {
+        let length = data.len() as u32;
+        let crc = kind.checksum(&chunk_type.bytes(), &data);
 
         Chunk {
             length,
@@ -86,6 +205,70 @@ impl Chunk {
         }
     }
     
+    // reads exactly one chunk, leaving the reader positioned at the start of the next one
+    pub fn from_reader<R: Read>(reader: &mut R) -> Result<Chunk> {
+        Chunk::read(reader, true)
+    }
+
+    // keeps whatever CRC is stored, validate_crc_with tells whether it's right
+    pub fn from_reader_unchecked<R: Read>(reader: &mut R) -> Result<Chunk> {
+        Chunk::read(reader, false)
+    }
+
+    fn read<R: Read>(reader: &mut R, check_crc: bool) -> Result<Chunk> {
+        let mut length_bytes = [0; 4];
+        reader.read_exact(&mut length_bytes)
+            .context("Unexpected end of input while reading chunk length")?;
+        let length = u32::from_be_bytes(length_bytes);
+        if Self::MAX_LENGTH < length {
+            bail!(ChunkError::LengthTooLarge(length as usize));
+        }
+
+        let mut chunk_type_bytes = [0; 4];
+        reader.read_exact(&mut chunk_type_bytes)
+            .context("Unexpected end of input while reading chunk type")?;
+        let chunk_type = ChunkType::from_bytes_relaxed(chunk_type_bytes)?;
+
+        // take() instead of a zeroed buffer so a bogus length can't allocate 2GB up front
+        let mut chunk_data = Vec::new();
+        reader.take(u64::from(length)).read_to_end(&mut chunk_data)?;
+        if chunk_data.len() != length as usize {
+            bail!(
+                "Unexpected end of input while reading chunk data: expected {length} bytes, got {}",
+                chunk_data.len()
+            );
+        }
+
+        let mut crc_bytes = [0; 4];
+        reader.read_exact(&mut crc_bytes)
+            .context("Unexpected end of input while reading chunk crc")?;
+        let crc = u32::from_be_bytes(crc_bytes);
+        if check_crc {
+            Self::validate_crc(crc, &chunk_data, &chunk_type.bytes(), CrcKind::default())?;
+        }
+
+        Ok(Chunk {
+            length,
+            chunk_type,
+            chunk_data,
+            crc,
+        })
+    }
+
+    // like new, but refuses data that doesn't fit the chunk length cap instead of
+    // letting `data.len() as u32` wrap
+    pub fn try_new(chunk_type: ChunkType, data: Vec<u8>) -> Result<Chunk> {
+        Self::check_length(data.len())?;
+        Ok(Self::new(chunk_type, data))
+    }
+
+    fn check_length(length: usize) -> Result<u32> {
+        u32::try_from(length)
+            .ok()
+            .filter(|&length| length <= Self::MAX_LENGTH)
+            .ok_or_else(|| ChunkError::LengthTooLarge(length).into())
+    }
+
     pub fn length(&self) -> u32 {
         self.length
     }
@@ -93,36 +276,95 @@ impl Chunk {
     pub fn chunk_type(&self) -> &ChunkType {
         &self.chunk_type
     }
+
+    // compares against the type as text without building a String, e.g. is_type("IDAT")
+    pub fn is_type(&self, chunk_type: &str) -> bool {
+        chunk_type.as_bytes() == self.chunk_type.bytes()
+    }
+
+    pub fn chunk_type_str(&self) -> String {
+        self.chunk_type.to_string()
+    }
     
-    fn data(&self) -> &[u8] {
+    pub fn data(&self) -> &[u8] {
         self.chunk_data.as_slice()
     }
     
-    fn crc(&self) -> u32 {
+    pub fn crc(&self) -> u32 {
         self.crc
     }
     
+    // for a chunk read with from_reader_unchecked, makes the stored CRC match again
+    pub fn recompute_crc(&mut self) {
+        self.crc = CrcKind::default().checksum(&self.chunk_type.bytes(), &self.chunk_data);
+    }
+
+    // the setters keep length and CRC in step with the type and data
+    pub fn set_data(&mut self, data: Vec<u8>) -> Result<()> {
+        self.length = Self::check_length(data.len())?;
+        self.chunk_data = data;
+        self.recompute_crc();
+        Ok(())
+    }
+
+    pub fn set_chunk_type(&mut self, chunk_type: ChunkType) {
+        self.chunk_type = chunk_type;
+        self.recompute_crc();
+    }
+
     pub fn data_as_string(&self) -> Result<String> {
         std::str::from_utf8(&self.chunk_data)
             .map(String::from)
             .map_err(|e| anyhow!("UTF-8 conversion error: {e}"))
     }
     
+    // the first PREVIEW_LENGTH bytes, quoted if they're UTF-8 and as hex otherwise
+    fn data_preview(&self) -> String {
+        let data = self.data();
+        let mut preview = match std::str::from_utf8(data) {
+            Ok(text) => {
+                let end = text
+                    .char_indices()
+                    .map(|(index, c)| index + c.len_utf8())
+                    .take_while(|&end| end <= Self::PREVIEW_LENGTH)
+                    .last()
+                    .unwrap_or(0);
+                format!("\"{}\"", text[..end].escape_debug())
+            }
+            Err(_) => data
+                .iter()
+                .take(Self::PREVIEW_LENGTH)
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        if data.len() > Self::PREVIEW_LENGTH {
+            preview.push_str("...");
+        }
+        preview
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let capacity = 4 + 4 + self.data().len() + 4;
         let mut result = Vec::with_capacity(capacity);
-        result.extend_from_slice(&self.length.to_be_bytes());
-        result.extend_from_slice(&self.chunk_type.bytes());
-        result.extend_from_slice(self.data());
-        result.extend_from_slice(&self.crc().to_be_bytes());
+        self.write_to(&mut result).expect("writing to a Vec can't fail");
         result
     }
 
-    fn validate_crc(crc: u32, data: &[u8], chunk_type: &[u8; 4]) -> Result<()> {
-        let mut digest = CRC_ALGO.digest();
-        digest.update(chunk_type);
-        digest.update(data);
-        let calculated_crc = digest.finalize();
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.length.to_be_bytes())?;
+        w.write_all(&self.chunk_type.bytes())?;
+        w.write_all(self.data())?;
+        w.write_all(&self.crc().to_be_bytes())
+    }
+
+    // checks the stored CRC against one computed with the given kind
+    pub fn validate_crc_with(&self, kind: CrcKind) -> Result<()> {
+        Self::validate_crc(self.crc, &self.chunk_data, &self.chunk_type.bytes(), kind)
+    }
+
+    fn validate_crc(crc: u32, data: &[u8], chunk_type: &[u8; 4], kind: CrcKind) -> Result<()> {
+        let calculated_crc = kind.checksum(chunk_type, data);
         if crc == calculated_crc {
             Ok(())
         } else {
@@ -138,6 +380,7 @@ impl Chunk {
 mod tests {
     use super::*;
     use crate::chunk_type::ChunkType;
+    use proptest::prelude::*;
     use std::str::FromStr;
 
     fn testing_chunk() -> Chunk {
@@ -155,7 +398,7 @@ mod tests {
             .copied()
             .collect();
 
-        Chunk::try_from(chunk_data.as_ref()).unwrap()
+        Chunk::try_from(&chunk_data).unwrap()
     }
 
     #[test]
@@ -167,6 +410,29 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_try_new_chunk() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = "This is where your secret message will be!".as_bytes().to_vec();
+        let chunk = Chunk::try_new(chunk_type, data).unwrap();
+        assert_eq!(chunk.crc(), 2882656334);
+    }
+
+    #[test]
+    fn test_check_length_too_large() {
+        assert_eq!(Chunk::check_length(42).unwrap(), 42);
+        assert_eq!(Chunk::check_length(Chunk::MAX_LENGTH as usize).unwrap(), Chunk::MAX_LENGTH);
+
+        // stands in for a message too big to allocate in a test
+        for length in [Chunk::MAX_LENGTH as usize + 1, u32::MAX as usize + 43] {
+            let error = Chunk::check_length(length).unwrap_err();
+            assert!(matches!(
+                error.downcast_ref::<ChunkError>(),
+                Some(ChunkError::LengthTooLarge(l)) if *l == length
+            ));
+        }
+    }
+
     #[test]
     fn test_chunk_length() {
         let chunk = testing_chunk();
@@ -193,6 +459,22 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_chunk_crc_kind() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = b"This is where your secret message will be!".to_vec();
+
+        let default = Chunk::new_with_crc_kind(chunk_type.clone(), data.clone(), CrcKind::default());
+        assert_eq!(default.crc(), 2882656334);
+        assert_eq!(default, testing_chunk());
+
+        let bzip2 = Chunk::new_with_crc_kind(chunk_type, data, CrcKind::Bzip2);
+        assert_ne!(bzip2.crc(), 2882656334);
+        assert!(bzip2.validate_crc_with(CrcKind::Bzip2).is_ok());
+        assert!(bzip2.validate_crc_with(CrcKind::IsoHdlc).is_err());
+        assert!(Chunk::try_from(bzip2.as_bytes().as_slice()).is_err());
+    }
+
     #[test]
     fn test_valid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -209,7 +491,7 @@ mod tests {
             .copied()
             .collect();
 
-        let chunk = Chunk::try_from(chunk_data.as_ref()).unwrap();
+        let chunk = Chunk::try_from(&chunk_data).unwrap();
 
         let chunk_string = chunk.data_as_string().unwrap();
         let expected_chunk_string = String::from("This is where your secret message will be!");
@@ -236,11 +518,128 @@ mod tests {
             .copied()
             .collect();
 
-        let chunk = Chunk::try_from(chunk_data.as_ref());
+        let chunk = Chunk::try_from(&chunk_data);
 
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_from_bytes_with_trailing_data() {
+        let data_length: u32 = 42;
+        let chunk_type = "RuSt".as_bytes();
+        let message_bytes = "This is where your secret message will be!".as_bytes();
+        let crc: u32 = 2882656334;
+        let next_chunk = [0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130];
+
+        let chunk_data: Vec<u8> = data_length
+            .to_be_bytes()
+            .iter()
+            .chain(chunk_type.iter())
+            .chain(message_bytes.iter())
+            .chain(crc.to_be_bytes().iter())
+            .chain(next_chunk.iter())
+            .copied()
+            .collect();
+
+        let chunk = Chunk::try_from(&chunk_data).unwrap();
+
+        assert_eq!(chunk.length(), 42);
+        assert_eq!(chunk.crc(), 2882656334);
+    }
+
+    #[test]
+    fn test_chunk_missing_crc() {
+        let chunk_data: Vec<u8> = 3u32
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes())
+            .chain("abc".as_bytes())
+            .chain([1, 2].iter())
+            .copied()
+            .collect();
+
+        let error = Chunk::try_from(&chunk_data).unwrap_err();
+
+        assert_eq!(error.to_string(), "Input slice is too short for chunk crc");
+    }
+
+    #[test]
+    fn test_chunk_from_reader() {
+        let chunk = testing_chunk();
+        let mut bytes = chunk.as_bytes();
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        let mut reader = std::io::Cursor::new(bytes);
+
+        let read = Chunk::from_reader(&mut reader).unwrap();
+
+        assert_eq!(read, chunk);
+        assert_eq!(reader.position(), 54);
+    }
+
+    #[test]
+    fn test_chunk_from_reader_truncated() {
+        let bytes = testing_chunk().as_bytes();
+
+        let error = Chunk::from_reader(&mut &bytes[..6]).unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected end of input while reading chunk type");
+
+        let error = Chunk::from_reader(&mut &bytes[..20]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unexpected end of input while reading chunk data: expected 42 bytes, got 12"
+        );
+
+        let error = Chunk::from_reader(&mut &bytes[..52]).unwrap_err();
+        assert_eq!(error.to_string(), "Unexpected end of input while reading chunk crc");
+    }
+
+    #[test]
+    fn test_chunk_write_to() {
+        let chunk = testing_chunk();
+        let mut written = Vec::new();
+        chunk.write_to(&mut written).unwrap();
+        assert_eq!(written, chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_chunk_length_too_large() {
+        let chunk_data: Vec<u8> = 2u32
+            .pow(31)
+            .to_be_bytes()
+            .iter()
+            .chain("RuSt".as_bytes())
+            .copied()
+            .collect();
+
+        let error = Chunk::try_from(&chunk_data).unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<ChunkError>(),
+            Some(ChunkError::LengthTooLarge(length)) if *length == 2usize.pow(31)
+        ));
+    }
+
+    #[test]
+    fn test_chunk_display() {
+        let chunk = testing_chunk();
+        let expected = "Chunk length: 42\n\
+            Chunk type: RuSt\n\
+            CRC: 2882656334\n\
+            Data: \"This is where your secret messag\"...\n";
+        assert_eq!(chunk.to_string(), expected);
+    }
+
+    #[test]
+    fn test_chunk_display_binary_data() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0xff, 0x00, 0x10]);
+        assert!(chunk.to_string().ends_with("Data: ff 00 10\n"));
+
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new(chunk_type, vec![]);
+        assert!(chunk.to_string().ends_with("Data: \"\"\n"));
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -257,8 +656,113 @@ mod tests {
             .copied()
             .collect();
 
-        let chunk: Chunk = TryFrom::try_from(chunk_data.as_ref()).unwrap();
+        let chunk: Chunk = TryFrom::try_from(&chunk_data).unwrap();
 
         let _chunk_string = format!("{}", chunk);
+
+        let bytes: Vec<u8> = (&chunk).into();
+        assert_eq!(bytes, chunk_data);
+        assert_eq!(Vec::from(chunk.clone()), chunk.as_bytes());
+        assert_eq!(Chunk::try_from(bytes.as_slice()).unwrap(), chunk);
+    }
+
+    #[test]
+    fn test_chunk_try_from_vec() {
+        let chunk = testing_chunk();
+        let bytes = chunk.as_bytes();
+        assert_eq!(Chunk::try_from(&bytes).unwrap(), chunk);
+        assert_eq!(Chunk::try_from(bytes).unwrap(), chunk);
+        assert!(Chunk::try_from(vec![0, 0, 0, 1]).is_err());
+    }
+
+    #[test]
+    fn test_is_type() {
+        let chunk = testing_chunk();
+        assert!(chunk.is_type("RuSt"));
+        assert_eq!(chunk.chunk_type_str(), "RuSt");
+        for other in ["rust", "RUST", "RuS", "RuStt", ""] {
+            assert!(!chunk.is_type(other), "{other}");
+        }
+    }
+
+    #[test]
+    fn test_setters_keep_chunk_consistent() {
+        let mut chunk = testing_chunk();
+        chunk.set_data(b"shorter".to_vec()).unwrap();
+        assert_eq!(chunk.length(), 7);
+        assert_eq!(chunk.data(), b"shorter");
+        assert_eq!(chunk, Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"shorter".to_vec()));
+
+        chunk.set_chunk_type(ChunkType::from_str("ruSt").unwrap());
+        assert!(chunk.validate_crc_with(CrcKind::default()).is_ok());
+        let rebuilt = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"shorter".to_vec());
+        assert_eq!(chunk.crc(), rebuilt.crc());
+
+        // a chunk kept with a bad CRC can be repaired in place
+        let mut bytes = chunk.as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let mut damaged = Chunk::from_reader_unchecked(&mut bytes.as_slice()).unwrap();
+        assert!(damaged.validate_crc_with(CrcKind::default()).is_err());
+        damaged.recompute_crc();
+        assert_eq!(damaged, chunk);
+    }
+
+    #[test]
+    fn test_chunk_serde() {
+        let chunk = testing_chunk();
+        let value = serde_json::to_value(&chunk).unwrap();
+        assert_eq!(value["type"], "RuSt");
+        assert_eq!(value["crc"], 2882656334u32);
+        assert_eq!(serde_json::from_value::<Chunk>(value.clone()).unwrap(), chunk);
+
+        // a missing crc is computed, a wrong one is refused
+        let mut without_crc = value.clone();
+        without_crc.as_object_mut().unwrap().remove("crc");
+        assert_eq!(serde_json::from_value::<Chunk>(without_crc).unwrap(), chunk);
+        let mut wrong_crc = value.clone();
+        wrong_crc["crc"] = 1.into();
+        assert!(serde_json::from_value::<Chunk>(wrong_crc).is_err());
+
+        let mut bad_type = value.clone();
+        bad_type["type"] = "Ru5t".into();
+        assert!(serde_json::from_value::<Chunk>(bad_type).is_err());
+        let mut bad_data = value;
+        bad_data["data"] = "not base64!".into();
+        assert!(serde_json::from_value::<Chunk>(bad_data).is_err());
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(4096))]
+
+        #[test]
+        fn test_chunk_round_trips(chunk in strategies::chunk()) {
+            prop_assert_eq!(Chunk::try_from(chunk.as_bytes().as_slice()).unwrap(), chunk);
+        }
+    }
+}
+
+// generators shared by the property tests here and in png
+#[cfg(test)]
+pub(crate) mod strategies {
+    use super::Chunk;
+    use crate::chunk_type::ChunkType;
+    use proptest::prelude::*;
+
+    // any printable ASCII, which is what the chunk parsers accept
+    pub fn chunk_type() -> impl Strategy<Value = ChunkType> {
+        prop::array::uniform4(b'!'..=b'~')
+            .prop_map(|bytes| ChunkType::from_bytes_relaxed(bytes).unwrap())
+    }
+
+    // the real cap is 2^31 - 1 bytes. Most cases stay under 4 KiB so they're quick, and the
+    // odd one goes past 64 KiB so the third length byte gets set too
+    pub fn chunk() -> impl Strategy<Value = Chunk> {
+        let data = prop_oneof![
+            15 => prop::collection::vec(any::<u8>(), 0..4096),
+            1 => prop::collection::vec(any::<u8>(), 65536..70000),
+        ];
+        (chunk_type(), data)
+            .prop_map(|(chunk_type, data)| Chunk::new(chunk_type, data))
     }
 }
@@ -3,26 +3,32 @@ use std::str::FromStr;
 use anyhow::{bail, Context, Result};
 use thiserror::Error;
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
 pub struct ChunkType {
     data: [u8; 4],
 }
 
 #[derive(Debug, Error)]
-enum ChunkTypeError {
+pub enum ChunkTypeError {
     #[error("String is not ASCII")]
     NonAscii,
     #[error("String is not 4 (actual length: {0})")]
     InvalidLength(usize),
     #[error("String contains non-ASCII letters")]
     NonAlphabetic,
+    #[error("Chunk type contains bytes that aren't printable ASCII")]
+    NonGraphic,
     
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
     type Error = anyhow::Error;
 
+    // same rule as from_str, so the property bit methods only ever see letters
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
+        if !value.iter().all(u8::is_ascii_alphabetic) {
+            bail!(ChunkTypeError::NonAlphabetic);
+        }
         Ok(ChunkType { data: value })
     }
 }
@@ -62,11 +68,99 @@ impl FromStr for ChunkType {
 
 impl Display for ChunkType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", std::str::from_utf8(&self.data).unwrap())
+        // letters print as-is, anything else is escaped instead of panicking on bad UTF-8
+        for byte in self.data {
+            write!(f, "{}", std::ascii::escape_default(byte))?;
+        }
+        Ok(())
+    }
+}
+
+// as the 4 character string, so JSON holds "ruSt" rather than an array of bytes
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
     }
 }
 
+impl<'de> serde::Deserialize<'de> for ChunkType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        ChunkType::from_str(&text).map_err(|error| serde::de::Error::custom(format!("{error:#}")))
+    }
+}
+
+// registered chunk types from the PNG spec (plus the APNG extension), what they hold
+// and whether a file may contain more than one of them
+static STANDARD_CHUNK_TYPES: &[([u8; 4], &str, bool)] = &[
+    (*b"IHDR", "Image header", false),
+    (*b"PLTE", "Palette", false),
+    (*b"IDAT", "Image data", true),
+    (*b"IEND", "Image trailer", false),
+    (*b"cHRM", "Primary chromaticities and white point", false),
+    (*b"gAMA", "Image gamma", false),
+    (*b"iCCP", "Embedded ICC profile", false),
+    (*b"sBIT", "Significant bits", false),
+    (*b"sRGB", "Standard RGB colour space", false),
+    (*b"cICP", "Coding-independent code points", false),
+    (*b"tEXt", "Textual data", true),
+    (*b"zTXt", "Compressed textual data", true),
+    (*b"iTXt", "International textual data", true),
+    (*b"bKGD", "Background colour", false),
+    (*b"hIST", "Image histogram", false),
+    (*b"tRNS", "Transparency", false),
+    (*b"pHYs", "Physical pixel dimensions", false),
+    (*b"sPLT", "Suggested palette", true),
+    (*b"tIME", "Image last-modification time", false),
+    (*b"eXIf", "Exchangeable image file format metadata", false),
+    (*b"acTL", "Animation control", false),
+    (*b"fcTL", "Frame control", true),
+    (*b"fdAT", "Frame data", true),
+];
+
 impl ChunkType {
+    pub const IHDR: ChunkType = ChunkType { data: *b"IHDR" };
+    pub const IDAT: ChunkType = ChunkType { data: *b"IDAT" };
+    pub const IEND: ChunkType = ChunkType { data: *b"IEND" };
+    pub const TIME: ChunkType = ChunkType { data: *b"tIME" };
+
+    // for interop with tools that use digits and the like in their types, any printable
+    // ASCII is accepted. Files are read with the same rule, so whatever encode writes
+    // parses again
+    pub fn from_str_relaxed(s: &str) -> Result<ChunkType> {
+        if !s.is_ascii() {
+            bail!(ChunkTypeError::NonAscii);
+        }
+        let data: [u8; 4] = s
+            .as_bytes()
+            .try_into()
+            .map_err(|_| ChunkTypeError::InvalidLength(s.len()))?;
+        ChunkType::from_bytes_relaxed(data)
+    }
+
+    // what the chunk parsers use, try_from stays letters only for types given by hand
+    pub fn from_bytes_relaxed(data: [u8; 4]) -> Result<ChunkType> {
+        if !data.iter().all(u8::is_ascii_graphic) {
+            bail!(ChunkTypeError::NonGraphic);
+        }
+        Ok(ChunkType { data })
+    }
+
+    // 8 hex digits for the 4 bytes, which must come out as printable ASCII like from_str_relaxed
+    pub fn from_hex(hex: &str) -> Result<ChunkType> {
+        if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            bail!("Invalid hex digits in chunk type {hex:?}");
+        }
+        if hex.len() != 8 {
+            bail!("Hex chunk type must be exactly 8 hex digits (got {})", hex.len());
+        }
+        let mut data = [0; 4];
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)?;
+        }
+        ChunkType::from_bytes_relaxed(data)
+    }
+
     pub fn length() -> u32 {
         4
     }
@@ -75,25 +169,78 @@ impl ChunkType {
         self.data
     }
     
-    fn is_valid(&self) -> bool {
+    // None for types that aren't in the PNG spec
+    pub fn description(&self) -> Option<&'static str> {
+        STANDARD_CHUNK_TYPES
+            .iter()
+            .find(|(bytes, ..)| bytes == &self.data)
+            .map(|&(_, description, _)| description)
+    }
+
+    // non-standard types have no such restriction
+    pub fn allows_multiple(&self) -> bool {
+        !STANDARD_CHUNK_TYPES
+            .iter()
+            .any(|(bytes, _, multiple)| bytes == &self.data && !multiple)
+    }
+
+    pub fn is_valid(&self) -> bool {
         self.data[2].is_ascii_uppercase()
     }
     
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         self.data[0].is_ascii_uppercase()
     }
     
-    fn is_public(&self) -> bool {
+    pub fn is_ancillary(&self) -> bool {
+        !self.is_critical()
+    }
+
+    pub fn is_public(&self) -> bool {
         self.data[1].is_ascii_uppercase()
     }
     
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         self.data[2].is_ascii_uppercase()
     }
     
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         self.data[3].is_ascii_lowercase()
     }
+
+    // the four case bits spelled out in order, e.g. "ancillary, private, reserved-valid, safe-to-copy"
+    pub fn property_string(&self) -> String {
+        let properties = [
+            if self.is_critical() { "critical" } else { "ancillary" },
+            if self.is_public() { "public" } else { "private" },
+            if self.is_reserved_bit_valid() { "reserved-valid" } else { "reserved-invalid" },
+            if self.is_safe_to_copy() { "safe-to-copy" } else { "unsafe-to-copy" },
+        ];
+        properties.join(", ")
+    }
+
+    // each property bit is the case of one byte, so flipping it keeps the byte a letter
+    pub fn with_critical(&self, critical: bool) -> ChunkType {
+        self.with_case(0, critical)
+    }
+
+    pub fn with_public(&self, public: bool) -> ChunkType {
+        self.with_case(1, public)
+    }
+
+    pub fn with_safe_to_copy(&self, safe_to_copy: bool) -> ChunkType {
+        self.with_case(3, !safe_to_copy)
+    }
+
+    fn with_case(&self, index: usize, uppercase: bool) -> ChunkType {
+        let mut data = self.data;
+        if uppercase {
+            data[index].make_ascii_uppercase();
+        } else {
+            data[index].make_ascii_lowercase();
+        }
+        ChunkType { data }
+    }
 }
 
 #[cfg(test)]
@@ -110,6 +257,14 @@ mod tests {
         assert_eq!(expected, actual.bytes());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_invalid_bytes() {
+        let error = ChunkType::try_from([1, 2, 3, 4]).unwrap_err();
+        assert!(matches!(error.downcast_ref::<ChunkTypeError>(), Some(ChunkTypeError::NonAlphabetic)));
+
+        assert!(ChunkType::try_from([82, 117, 49, 116]).is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_from_str() {
         let expected = ChunkType::try_from([82, 117, 83, 116]).unwrap();
@@ -117,6 +272,32 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    pub fn test_chunk_type_from_str_relaxed() {
+        assert!(ChunkType::from_str("Ru1t").is_err());
+        let chunk = ChunkType::from_str_relaxed("Ru1t").unwrap();
+        assert_eq!(&chunk.to_string(), "Ru1t");
+
+        assert!(ChunkType::from_str_relaxed("Ru1").is_err());
+        assert!(ChunkType::from_str_relaxed("Ru1tt").is_err());
+        assert!(ChunkType::from_str_relaxed("Rü1").is_err());
+        assert!(ChunkType::from_str_relaxed("Ru t").is_err());
+
+        assert_eq!(ChunkType::from_bytes_relaxed(*b"Ru1t").unwrap(), chunk);
+        let error = ChunkType::from_bytes_relaxed([b'R', 0, b'S', b't']).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(ChunkTypeError::NonGraphic)));
+    }
+
+    #[test]
+    pub fn test_chunk_type_from_hex() {
+        assert_eq!(ChunkType::from_hex("52755374").unwrap(), ChunkType::from_str("RuSt").unwrap());
+        assert_eq!(ChunkType::from_hex("52753174").unwrap().bytes(), *b"Ru1t");
+        assert!(ChunkType::from_hex("527553").is_err());
+        assert!(ChunkType::from_hex("5275537g").is_err());
+        assert!(ChunkType::from_hex("+2755374").is_err());
+        assert!(ChunkType::from_hex("52000074").is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_is_critical() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -129,6 +310,12 @@ mod tests {
         assert!(!chunk.is_critical());
     }
 
+    #[test]
+    pub fn test_chunk_type_is_ancillary() {
+        assert!(ChunkType::from_str("ruSt").unwrap().is_ancillary());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_ancillary());
+    }
+
     #[test]
     pub fn test_chunk_type_is_public() {
         let chunk = ChunkType::from_str("RUSt").unwrap();
@@ -165,6 +352,32 @@ mod tests {
         assert!(!chunk.is_safe_to_copy());
     }
 
+    #[test]
+    pub fn test_chunk_type_with_bits() {
+        let chunk = ChunkType::from_str("ruSt").unwrap();
+
+        let unsafe_to_copy = chunk.with_safe_to_copy(false);
+        assert!(!unsafe_to_copy.is_safe_to_copy());
+        assert_eq!(&unsafe_to_copy.to_string(), "ruST");
+        assert_eq!(&unsafe_to_copy.with_safe_to_copy(true).to_string(), "ruSt");
+
+        let critical = chunk.with_critical(true);
+        assert!(critical.is_critical());
+        assert_eq!(&critical.to_string(), "RuSt");
+        assert_eq!(&chunk.with_public(true).to_string(), "rUSt");
+        assert_eq!(chunk.with_critical(false), chunk);
+    }
+
+    #[test]
+    pub fn test_chunk_type_property_string() {
+        let properties = |chunk_type| ChunkType::from_str(chunk_type).unwrap().property_string();
+        assert_eq!(properties("RuSt"), "critical, private, reserved-valid, safe-to-copy");
+        assert_eq!(properties("ruSt"), "ancillary, private, reserved-valid, safe-to-copy");
+        assert_eq!(properties("RUST"), "critical, public, reserved-valid, unsafe-to-copy");
+        assert_eq!(properties("RusT"), "critical, private, reserved-invalid, unsafe-to-copy");
+        assert_eq!(properties("tEXt"), "ancillary, public, reserved-valid, safe-to-copy");
+    }
+
     #[test]
     pub fn test_valid_chunk_is_valid() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -186,6 +399,31 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn test_chunk_type_string_non_alphabetic() {
+        // can't be built through try_from or from_str anymore, so construct it directly
+        let chunk = ChunkType { data: [0xff, b'R', 0, b't'] };
+        assert_eq!(&chunk.to_string(), "\\xffR\\x00t");
+    }
+
+    #[test]
+    pub fn test_chunk_type_description() {
+        let chunk = ChunkType::from_str("gAMA").unwrap();
+        assert_eq!(chunk.description(), Some("Image gamma"));
+
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(chunk.description(), None);
+    }
+
+    #[test]
+    pub fn test_chunk_type_allows_multiple() {
+        assert!(ChunkType::from_str("IDAT").unwrap().allows_multiple());
+        assert!(ChunkType::from_str("tEXt").unwrap().allows_multiple());
+        assert!(ChunkType::from_str("RuSt").unwrap().allows_multiple());
+        assert!(!ChunkType::from_str("IHDR").unwrap().allows_multiple());
+        assert!(!ChunkType::from_str("IEND").unwrap().allows_multiple());
+    }
+
     #[test]
     pub fn test_chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
@@ -193,4 +431,16 @@ mod tests {
         let _chunk_string = format!("{}", chunk_type_1);
         let _are_chunks_equal = chunk_type_1 == chunk_type_2;
     }
+
+    #[test]
+    fn test_chunk_type_serde() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let json = serde_json::to_string(&chunk_type).unwrap();
+        assert_eq!(json, "\"ruSt\"");
+        assert_eq!(serde_json::from_str::<ChunkType>(&json).unwrap(), chunk_type);
+
+        assert!(serde_json::from_str::<ChunkType>("\"ru5t\"").is_err());
+        assert!(serde_json::from_str::<ChunkType>("\"rust!\"").is_err());
+        assert!(serde_json::from_str::<ChunkType>("[114, 117, 83, 116]").is_err());
+    }
 }
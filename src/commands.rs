@@ -0,0 +1,3614 @@
+use std::fmt::{Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use indicatif::ProgressBar;
+use log::{info, warn};
+use owo_colors::OwoColorize;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use walkdir::WalkDir;
+
+use crate::args::{DecodeArgs, DecodeFormat, EncodeArgs, OutputFormat, PrintArgs, WriteArgs};
+use crate::chunk::{Chunk, CrcKind};
+use crate::chunk_type::ChunkType;
+use crate::envelope;
+use crate::metadata::{
+    AnimationControl, Gamma, IccProfile, ImageHeader, LastModified, Palette, PhysicalDimensions,
+    TextEntry,
+};
+use crate::png::{Png, PngError};
+use crate::progress;
+use crate::repl::Repl;
+
+// resolves the chunk type given on the command line, either as text or as --type-hex
+pub fn parse_chunk_type(
+    chunk_type: Option<&str>,
+    type_hex: Option<&str>,
+    allow_nonstandard: bool,
+) -> Result<ChunkType> {
+    match (chunk_type, type_hex) {
+        (Some(_), Some(_)) => bail!("Pass either a chunk type or --type-hex, not both"),
+        (None, Some(hex)) => ChunkType::from_hex(hex),
+        (Some(chunk_type), None) if allow_nonstandard => ChunkType::from_str_relaxed(chunk_type),
+        (Some(chunk_type), None) => ChunkType::from_str(chunk_type),
+        (None, None) => bail!("Missing chunk type, pass one, use --type-hex or set chunk_type in pngme.toml"),
+    }
+}
+
+// custom data belongs in an ancillary, safe-to-copy chunk: decoders may skip it and editors
+// carry it over. A critical type can make the image unreadable and is refused unless
+// explicitly allowed, and still warned about then. --no-check skips all of this
+pub fn check_encode_type(chunk_type: &ChunkType, allow_critical: bool, no_check: bool) -> Result<()> {
+    if no_check {
+        return Ok(());
+    }
+    if !chunk_type.is_ancillary() {
+        if !allow_critical {
+            bail!(
+                "{chunk_type} is a critical chunk type and may break image decoders, use a lowercase \
+                 first letter or pass --allow-critical"
+            );
+        }
+        warn!("{chunk_type} is a critical chunk type, decoders that don't know it will reject the image");
+    }
+    if !chunk_type.is_safe_to_copy() {
+        bail!(
+            "{chunk_type} isn't safe to copy, so editors drop it when they change the image, use a \
+             lowercase last letter or pass --no-check"
+        );
+    }
+    Ok(())
+}
+
+// the message is only wrapped in an envelope when a transform is asked for, so plain
+// messages stay readable by other tools
+pub fn encode(
+    file_path: &Path,
+    chunk_type: &ChunkType,
+    message: &str,
+    options: &EncodeArgs,
+    output: Option<&Path>,
+    write: &WriteArgs,
+) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let transforms = options.transforms();
+    let data = if transforms.is_empty() && options.sign.is_none() {
+        message.as_bytes().to_vec()
+    } else {
+        envelope::wrap_with(message.as_bytes(), transforms, options.key(), options.deflate_level)?
+    };
+    let stored = data.len();
+    if let Some(size) = options.fragment {
+        let fragments = envelope::fragment(&data, usize::try_from(size).unwrap_or(usize::MAX))?;
+        info!("Splitting the message across {} {chunk_type} chunks", fragments.len());
+        for fragment in fragments {
+            png.insert_before_iend(Chunk::try_new(chunk_type.clone(), fragment)?);
+        }
+    } else {
+        let chunk = Chunk::try_new(chunk_type.clone(), data)?;
+        let after = options.after.as_deref().map(ChunkType::from_str).transpose()?;
+        if options.replace && png.remove_first_of_type(chunk_type).is_some() {
+            info!("Replacing the existing {chunk_type} chunk");
+        }
+        match after {
+            None => png.insert_before_iend(chunk),
+            Some(after) => {
+                if let Err(chunk) = png.insert_after_type(&after, chunk) {
+                    if !options.fallback {
+                        return Err(PngError::ChunkNotFound(after.to_string())).context(
+                            "Nothing to insert the message after, pass --fallback to put it before IEND",
+                        );
+                    }
+                    warn!("No {after} chunk in {}, inserting before IEND instead", file_path.display());
+                    png.insert_before_iend(chunk);
+                }
+            }
+        }
+    }
+
+    if let Some(source_path) = &options.append_file {
+        let appended = append_ancillary(&Png::from_file(source_path)?, &mut png);
+        info!("Appended {appended} chunks from {}", source_path.display());
+    }
+
+    let output = output_path(file_path, output, write)?;
+    write_png(&png, output, write)?;
+    let message = format!("Encoded message into {}", output.display());
+    let fields = [
+        ("type", chunk_type.to_string()),
+        ("bytes", stored.to_string()),
+        ("chunks", png.chunk_count().to_string()),
+    ];
+    report_with_fields(write, "encode", output, message, "encoded", &fields);
+    Ok(())
+}
+
+// with `verify` the file is parsed leniently, so a chunk with a bad CRC is still decoded but
+// warned about instead of failing the whole parse
+pub fn decode(
+    file_path: &Path,
+    chunk_type: &ChunkType,
+    options: &DecodeArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let png = if options.verify && !is_url(file_path) {
+        Png::from_file_lenient(file_path)?
+    } else {
+        load_readonly(file_path)?
+    };
+    let matching = matching_chunks(&png, chunk_type, options.ignore_case);
+    if matching.is_empty() {
+        bail!(PngError::ChunkNotFound(chunk_type.to_string()));
+    }
+    // from here on reports name the type actually found
+    let chunk_type = matching[0].chunk_type();
+    if options.ignore_case {
+        let mut found: Vec<String> = Vec::new();
+        for chunk in &matching {
+            let found_type = chunk.chunk_type().to_string();
+            if !found.contains(&found_type) {
+                found.push(found_type);
+            }
+        }
+        warn!("--ignore-case matched {}", found.join(", "));
+    }
+    if envelope::is_fragment(matching[0].data()) {
+        return decode_fragments(&matching, chunk_type, options, format);
+    }
+    let shown = match (options.all, options.count) {
+        (false, _) => 1,
+        (true, Some(count)) => count.min(matching.len()),
+        (true, None) => matching.len(),
+    };
+
+    if options.format == DecodeFormat::Raw {
+        if format == OutputFormat::Json {
+            bail!("--format raw can't be combined with --json");
+        }
+        if options.all {
+            bail!("--format raw can't be combined with --all, the messages would run together");
+        }
+        let chunk = matching[0];
+        if let Some(problem) = options.verify.then(|| crc_problem(chunk)).flatten() {
+            warn!("{problem}");
+        }
+        return write_raw(&envelope::unwrap_signed(chunk.data(), options.key())?);
+    }
+
+    let messages = decode_messages(&matching[..shown], options)?;
+    let stdout = io::stdout();
+    print_messages(&mut stdout.lock(), chunk_type, &messages, matching.len() - shown, options, format)
+}
+
+// chunk types are case sensitive, `ignore_case` is for when the exact casing is forgotten
+fn matching_chunks<'a>(png: &'a Png, chunk_type: &ChunkType, ignore_case: bool) -> Vec<&'a Chunk> {
+    png.chunks()
+        .iter()
+        .filter(|chunk| match ignore_case {
+            true => chunk.chunk_type().bytes().eq_ignore_ascii_case(&chunk_type.bytes()),
+            false => chunk.chunk_type() == chunk_type,
+        })
+        .collect()
+}
+
+// every fragment of the type goes into the one message
+fn decode_fragments(
+    chunks: &[&Chunk],
+    chunk_type: &ChunkType,
+    options: &DecodeArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    let (data, crc_ok) = join_fragments(chunks, options.verify, options.key())
+        .with_context(|| format!("Failed to join the {chunk_type} fragments"))?;
+    if options.format == DecodeFormat::Raw {
+        if format == OutputFormat::Json {
+            bail!("--format raw can't be combined with --json");
+        }
+        return write_raw(&data);
+    }
+    let message = String::from_utf8(render_message(&data, options.format)?)?;
+    let stdout = io::stdout();
+    print_messages(&mut stdout.lock(), chunk_type, &[(message, crc_ok)], 0, options, format)
+}
+
+// the unwrapped message and whether every fragment's CRC checked out. Chunks of the type
+// that aren't fragments are skipped
+fn join_fragments(chunks: &[&Chunk], verify: bool, key: Option<&[u8]>) -> Result<(Vec<u8>, bool)> {
+    let fragments: Vec<&Chunk> =
+        chunks.iter().copied().filter(|chunk| envelope::is_fragment(chunk.data())).collect();
+    let mut crc_ok = true;
+    for problem in fragments.iter().filter_map(|chunk| verify.then(|| crc_problem(chunk)).flatten()) {
+        warn!("{problem}");
+        crc_ok = false;
+    }
+    let data: Vec<&[u8]> = fragments.iter().map(|chunk| chunk.data()).collect();
+    Ok((envelope::unwrap_signed(&envelope::reassemble(&data)?, key)?, crc_ok))
+}
+
+fn write_raw(data: &[u8]) -> Result<()> {
+    let stdout = io::stdout();
+    if stdout.is_terminal() {
+        bail!("Refusing to write raw bytes to a terminal, redirect stdout to a file");
+    }
+    let mut out = stdout.lock();
+    out.write_all(data)?;
+    out.flush()?;
+    Ok(())
+}
+
+// each message along with whether its CRC checked out, always true without --verify
+fn decode_messages(chunks: &[&Chunk], options: &DecodeArgs) -> Result<Vec<(String, bool)>> {
+    let mut messages = Vec::with_capacity(chunks.len());
+    for chunk in chunks {
+        let crc_problem = if options.verify { crc_problem(chunk) } else { None };
+        if let Some(problem) = &crc_problem {
+            warn!("{problem}");
+        }
+        let data = envelope::unwrap_signed(chunk.data(), options.key())?;
+        let message = String::from_utf8(render_message(&data, options.format)?)?;
+        messages.push((message, crc_problem.is_none()));
+    }
+    Ok(messages)
+}
+
+// the single message form is kept as it was before --all, so scripts reading it don't break
+fn print_messages<W: Write>(
+    out: &mut W,
+    chunk_type: &ChunkType,
+    messages: &[(String, bool)],
+    not_shown: usize,
+    options: &DecodeArgs,
+    format: OutputFormat,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for (message, _) in messages {
+                writeln!(out, "{message}")?;
+            }
+            if options.all && not_shown > 0 {
+                writeln!(out, "note: {not_shown} more {chunk_type} chunks not shown")?;
+            }
+        }
+        OutputFormat::Json if options.all => {
+            let entries: Vec<Value> = messages
+                .iter()
+                .map(|(message, crc_ok)| {
+                    let mut entry = json!({ "message": message });
+                    if options.verify {
+                        entry["crc_ok"] = Value::from(*crc_ok);
+                    }
+                    entry
+                })
+                .collect();
+            let report = json!({
+                "chunk_type": chunk_type.to_string(),
+                "messages": entries,
+                "not_shown": not_shown,
+            });
+            writeln!(out, "{report}")?;
+        }
+        OutputFormat::Json => {
+            let (message, crc_ok) = &messages[0];
+            let mut report = json!({ "chunk_type": chunk_type.to_string(), "message": message });
+            if options.verify {
+                report["crc_ok"] = Value::from(*crc_ok);
+            }
+            writeln!(out, "{report}")?;
+        }
+    }
+    Ok(())
+}
+
+// the warning to show for a chunk whose stored CRC doesn't match its contents
+fn crc_problem(chunk: &Chunk) -> Option<String> {
+    let error = chunk.validate_crc_with(CrcKind::default()).err()?;
+    Some(format!(
+        "{} chunk failed CRC verification, the message may be corrupt: {error}",
+        chunk.chunk_type()
+    ))
+}
+
+// only utf8 can fail, every other format can represent any bytes
+pub(crate) fn render_message(data: &[u8], format: DecodeFormat) -> Result<Vec<u8>> {
+    match format {
+        DecodeFormat::Utf8 => match std::str::from_utf8(data) {
+            Ok(_) => Ok(data.to_vec()),
+            Err(e) => bail!("UTF-8 conversion error: {e}, try --format hex or base64"),
+        },
+        DecodeFormat::Hex => {
+            let hex: String = data.iter().map(|byte| format!("{byte:02x}")).collect();
+            Ok(hex.into_bytes())
+        }
+        DecodeFormat::Base64 => Ok(BASE64.encode(data).into_bytes()),
+        DecodeFormat::Raw => Ok(data.to_vec()),
+    }
+}
+
+// the data is written exactly as stored, nothing is decoded
+pub fn extract(
+    file_path: &Path,
+    chunk_type: &str,
+    output_path: &Path,
+    force: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    let mut matching = png.chunks_of_type(&chunk_type);
+    let chunk = matching
+        .next()
+        .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+
+    check_output(file_path, output_path, force)?;
+    fs::write(output_path, chunk.data())
+        .with_context(|| format!("Failed to write {}", output_path.display()))?;
+
+    let remaining = matching.count();
+    if format == OutputFormat::Json {
+        let status = json!({
+            "command": "extract",
+            "file": file_path.display().to_string(),
+            "status": "ok",
+            "chunk_type": chunk_type.to_string(),
+            "bytes": chunk.length(),
+            "output": output_path.display().to_string(),
+            "not_extracted": remaining,
+        });
+        println!("{status}");
+        return Ok(());
+    }
+    println!("Wrote {} bytes of {chunk_type} data to {}", chunk.length(), output_path.display());
+    if remaining > 0 {
+        println!("note: {remaining} more {chunk_type} chunks were not extracted");
+    }
+    Ok(())
+}
+
+// unlike extract the profile is decompressed, so the output is a usable .icc file
+pub fn extract_icc(
+    file_path: &Path,
+    output_path: &Path,
+    force: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    let chunk = png
+        .chunk_by_type("iCCP")
+        .ok_or_else(|| PngError::ChunkNotFound("iCCP".to_string()))
+        .with_context(|| format!("{} has no embedded ICC profile", file_path.display()))?;
+    let icc = IccProfile::from_chunk(chunk)?;
+
+    check_output(file_path, output_path, force)?;
+    fs::write(output_path, &icc.profile)
+        .with_context(|| format!("Failed to write {}", output_path.display()))?;
+    match format {
+        OutputFormat::Text => println!(
+            "Wrote {} bytes of ICC profile \"{}\" to {}",
+            icc.profile.len(),
+            icc.name,
+            output_path.display()
+        ),
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "command": "extract-icc",
+                "file": file_path.display().to_string(),
+                "status": "ok",
+                "profile_name": icc.name,
+                "bytes": icc.profile.len(),
+                "output": output_path.display().to_string(),
+            })
+        ),
+    }
+    Ok(())
+}
+
+// every chunk as its type and base64 data, in file order. Lengths and CRCs are left out
+// since import recomputes them
+pub fn export(
+    file_path: &Path,
+    output_path: &Path,
+    force: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    if !png.trailing_bytes().is_empty() {
+        warn!("{} bytes after IEND are not exported", png.trailing_bytes().len());
+    }
+
+    check_output(file_path, output_path, force)?;
+    let text = serde_json::to_string_pretty(&chunks_to_json(png.chunks()))?;
+    fs::write(output_path, text + "\n")
+        .with_context(|| format!("Failed to write {}", output_path.display()))?;
+    match format {
+        OutputFormat::Text => println!(
+            "Exported {} chunks to {}",
+            png.chunks().len(),
+            output_path.display()
+        ),
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "command": "export",
+                "file": file_path.display().to_string(),
+                "status": "ok",
+                "chunks": png.chunks().len(),
+                "output": output_path.display().to_string(),
+            })
+        ),
+    }
+    Ok(())
+}
+
+// the inverse of export, the PNG signature, lengths and CRCs are all written fresh
+pub fn import(json_path: &Path, output_path: &Path, write: &WriteArgs) -> Result<()> {
+    let text = fs::read_to_string(json_path)
+        .with_context(|| format!("Failed to read {}", json_path.display()))?;
+    let value: Value = serde_json::from_str(&text)
+        .with_context(|| format!("{} isn't valid JSON", json_path.display()))?;
+    let png = png_from_json(&value).with_context(|| format!("Failed to import {}", json_path.display()))?;
+    if let Some(problem) = structure_problem(&png) {
+        warn!("{problem}, most decoders will reject {}", output_path.display());
+    }
+
+    check_output(json_path, output_path, write.force)?;
+    write_png(&png, output_path, write)?;
+    let message = format!("Imported {} chunks into {}", png.chunks().len(), output_path.display());
+    report(write, "import", output_path, message);
+    Ok(())
+}
+
+// the type goes in as its raw bytes rather than Display, which escapes quotes and
+// backslashes. Every parser only allows printable ASCII, so the bytes are valid text
+fn chunks_to_json(chunks: &[Chunk]) -> Value {
+    let chunks: Vec<Value> = chunks
+        .iter()
+        .map(|chunk| {
+            json!({
+                "type": String::from_utf8_lossy(&chunk.chunk_type().bytes()),
+                "data": BASE64.encode(chunk.data()),
+            })
+        })
+        .collect();
+    json!({ "chunks": chunks })
+}
+
+fn png_from_json(value: &Value) -> Result<Png> {
+    let Some(entries) = value.get("chunks").and_then(Value::as_array) else {
+        bail!("Expected an object with a \"chunks\" array");
+    };
+    let mut chunks = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let field = |name: &str| {
+            entry
+                .get(name)
+                .and_then(Value::as_str)
+                .with_context(|| format!("Chunk {index} has no \"{name}\" string"))
+        };
+        let chunk_type = ChunkType::from_str_relaxed(field("type")?)
+            .with_context(|| format!("Chunk {index} has an invalid type"))?;
+        let data = BASE64
+            .decode(field("data")?)
+            .with_context(|| format!("Chunk {index} data isn't valid base64"))?;
+        chunks.push(Chunk::try_new(chunk_type, data)?);
+    }
+    Ok(Png::from_chunks(chunks))
+}
+
+// a JSON file written by export or split, any other path is read as a PNG
+fn load_chunk_source(path: &Path) -> Result<Png> {
+    if path.extension().is_none_or(|extension| extension != "json") {
+        return Png::from_file(path);
+    }
+    let text =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let value: Value =
+        serde_json::from_str(&text).with_context(|| format!("{} isn't valid JSON", path.display()))?;
+    png_from_json(&value).with_context(|| format!("Failed to load chunks from {}", path.display()))
+}
+
+// the critical chunks go to a minimal image and the ancillary ones to a sidecar in the
+// export format, merge puts them back
+pub fn split(file_path: &Path, image_path: &Path, sidecar_path: &Path, write: &WriteArgs) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    if image_path == sidecar_path {
+        bail!("The image and the sidecar need different paths");
+    }
+    check_output(file_path, image_path, write.force)?;
+    check_output(file_path, sidecar_path, write.force)?;
+
+    let removed = png.strip_ancillary();
+    let text = serde_json::to_string_pretty(&chunks_to_json(&removed))?;
+    write_png(&png, image_path, write)?;
+    if !write.dry_run {
+        fs::write(sidecar_path, text + "\n")
+            .with_context(|| format!("Failed to write {}", sidecar_path.display()))?;
+    }
+    let message = format!(
+        "Wrote {} critical chunks to {} and {} ancillary chunks to {}",
+        png.chunks().len(),
+        image_path.display(),
+        removed.len(),
+        sidecar_path.display()
+    );
+    let fields = [
+        ("critical", png.chunks().len().to_string()),
+        ("ancillary", removed.len().to_string()),
+        ("sidecar", sidecar_path.display().to_string()),
+    ];
+    report_with_fields(write, "split", image_path, message, "split", &fields);
+    Ok(())
+}
+
+// hand edits can easily lose or move the chunks every PNG needs
+fn structure_problem(png: &Png) -> Option<&'static str> {
+    let chunks = png.chunks();
+    if chunks.first().map(|chunk| chunk.chunk_type()) != Some(&ChunkType::IHDR) {
+        return Some("The first chunk isn't IHDR");
+    }
+    if chunks.last().map(|chunk| chunk.chunk_type()) != Some(&ChunkType::IEND) {
+        return Some("The last chunk isn't IEND");
+    }
+    None
+}
+
+// wraps arbitrary bytes from a file, or hex digits given directly, in a new chunk, the
+// inverse of extract
+pub fn inject(
+    file_path: &Path,
+    chunk_type: &str,
+    payload_path: Option<&Path>,
+    data_hex: Option<&str>,
+    write: &WriteArgs,
+) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    let (payload, source) = match (payload_path, data_hex) {
+        (Some(payload_path), None) => {
+            let payload = fs::read(payload_path)
+                .with_context(|| format!("Failed to read {}", payload_path.display()))?;
+            (payload, payload_path.display().to_string())
+        }
+        (None, Some(hex)) => (parse_hex(hex)?, "The hex data".to_string()),
+        _ => bail!("Give either a payload file or --data-hex"),
+    };
+
+    let length = payload.len();
+    let chunk = Chunk::try_new(chunk_type.clone(), payload)
+        .with_context(|| format!("{source} doesn't fit in a chunk"))?;
+    png.insert_before_iend(chunk);
+    write_png(&png, file_path, write)?;
+    let message =
+        format!("Injected {length} bytes as a {chunk_type} chunk into {}", file_path.display());
+    report(write, "inject", file_path, message);
+    Ok(())
+}
+
+pub fn remove(file_path: &Path, chunk_type: &ChunkType, write: &WriteArgs) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let removed = png
+        .remove_first_of_type(chunk_type)
+        .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+    write_png(&png, file_path, write)?;
+    let message = format!("Removed {} chunk from {}", removed.chunk_type(), file_path.display());
+    let fields = [
+        ("type", removed.chunk_type().to_string()),
+        ("bytes", removed.data().len().to_string()),
+        ("chunks", png.chunk_count().to_string()),
+    ];
+    report_with_fields(write, "remove", file_path, message, "removed", &fields);
+    Ok(())
+}
+
+// several files are parsed in parallel and printed in the order given, one file failing
+// doesn't stop the others
+pub fn print(file_paths: &[PathBuf], options: &PrintArgs, format: OutputFormat) -> Result<()> {
+    let file_paths = &expand_paths(file_paths)?;
+    let batch = file_paths.len() > 1;
+    let stdout = io::stdout();
+    let terminal = stdout.is_terminal();
+    let mut out = stdout.lock();
+    let mut reports = Vec::new();
+    let mut failed = 0;
+
+    for (path, result) in file_paths.iter().zip(run_batch(file_paths, load_readonly)) {
+        let png = match result {
+            Ok(png) => png,
+            Err(error) if !batch => return Err(error),
+            Err(error) => {
+                failed += 1;
+                reports.extend(batch_failure(path, &error, format));
+                continue;
+            }
+        };
+        match format {
+            OutputFormat::Json => {
+                let mut report = print_report(&png, options);
+                report["file"] = json!(path.display().to_string());
+                reports.push(report);
+            }
+            OutputFormat::Text => {
+                if batch {
+                    writeln!(out, "{}:", path.display())?;
+                }
+                print_to(&mut out, terminal, &png, options)?;
+            }
+        }
+    }
+    if format == OutputFormat::Json {
+        writeln!(out, "{}", batch_json(reports))?;
+    }
+    batch_outcome(failed, file_paths.len())
+}
+
+// a terminal gets the labelled layout, anything else one tab separated line per chunk so
+// the output can be fed to grep and cut
+fn print_to(out: &mut impl Write, terminal: bool, png: &Png, options: &PrintArgs) -> io::Result<()> {
+    let offsets = png.chunk_offsets();
+    // filtered out chunks keep their place in the numbering
+    let shown: Vec<(usize, &Chunk)> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| options.shows(chunk.chunk_type()))
+        .collect();
+    if !terminal {
+        for &(index, chunk) in &shown {
+            let chunk_type = chunk.chunk_type();
+            let name = paint_type(chunk_type, options.color);
+            write!(out, "{}\t{name}\t{}", index + 1, chunk.length())?;
+            if options.flags {
+                write!(out, "\t{}", chunk_type.property_string())?;
+            }
+            if options.offsets {
+                write!(out, "\t{}", offsets[index])?;
+            }
+            writeln!(out)?;
+        }
+        if !png.trailing_bytes().is_empty() {
+            let trailing = png.trailing_bytes().len();
+            writeln!(out, "trailing\t{trailing}\t{}", png.trailing_offset())?;
+        }
+        return Ok(());
+    }
+
+    // without any options this is the same as the Png Display impl, types painted aside
+    let detailed = options.flags || options.offsets || options.is_filtered();
+    writeln!(out, "PNG Image:")?;
+    if options.is_filtered() {
+        writeln!(out, "Number of chunks: {} ({} shown)", png.chunks().len(), shown.len())?;
+    } else {
+        writeln!(out, "Number of chunks: {}", png.chunks().len())?;
+    }
+    if detailed {
+        writeln!(out, "Size: {}", format_size(png.total_size() as u64, options.bytes))?;
+    }
+    for &(index, chunk) in &shown {
+        let chunk_type = chunk.chunk_type();
+        write!(out, "Chunk {}: {}", index + 1, paint_type(chunk_type, options.color))?;
+        if !detailed {
+            writeln!(out)?;
+            continue;
+        }
+        write!(out, " ({})", format_size(chunk.length().into(), options.bytes))?;
+        if options.flags {
+            write!(out, " [{}]", chunk_type.property_string())?;
+        }
+        if options.offsets {
+            write!(out, " at offset {}", offsets[index])?;
+        }
+        writeln!(out)?;
+    }
+    if !png.trailing_bytes().is_empty() {
+        writeln!(
+            out,
+            "Trailing data: {} bytes after IEND at offset {}",
+            png.trailing_bytes().len(),
+            png.trailing_offset()
+        )?;
+    }
+    Ok(())
+}
+
+fn paint_type(chunk_type: &ChunkType, color: bool) -> String {
+    if color {
+        chunk_type.cyan().to_string()
+    } else {
+        chunk_type.to_string()
+    }
+}
+
+// the property bits and offsets are always included, there's no separate --flags form.
+// --types and --exclude apply here too
+fn print_report(png: &Png, options: &PrintArgs) -> Value {
+    let chunks: Vec<Value> = png
+        .chunks()
+        .iter()
+        .zip(png.chunk_offsets())
+        .enumerate()
+        .filter(|(_, (chunk, _))| options.shows(chunk.chunk_type()))
+        .map(|(index, (chunk, offset))| {
+            let chunk_type = chunk.chunk_type();
+            json!({
+                "index": index,
+                "type": chunk_type.to_string(),
+                "offset": offset,
+                "length": chunk.length(),
+                "crc": chunk.crc(),
+                "critical": chunk_type.is_critical(),
+                "public": chunk_type.is_public(),
+                "reserved_bit_valid": chunk_type.is_reserved_bit_valid(),
+                "safe_to_copy": chunk_type.is_safe_to_copy(),
+            })
+        })
+        .collect();
+    json!({ "chunks": chunks, "trailing_bytes": png.trailing_bytes().len() })
+}
+
+// counts every chunk, or only the chunks of one type
+pub fn count(file_path: &Path, chunk_type: Option<&str>, format: OutputFormat) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    let chunk_type = chunk_type.map(ChunkType::from_str).transpose()?;
+    match (format, &chunk_type) {
+        (OutputFormat::Json, _) => println!("{}", count_report(&png, chunk_type.as_ref())),
+        (OutputFormat::Text, Some(chunk_type)) => {
+            println!("{} {chunk_type} chunks", png.chunks_of_type(chunk_type).count())
+        }
+        (OutputFormat::Text, None) => println!("{} chunks", png.chunks().len()),
+    }
+    Ok(())
+}
+
+// the same byte search for text and binary chunks, the pattern is UTF-8 unless --hex
+pub fn grep(file_path: &Path, pattern: &str, hex: bool, format: OutputFormat) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    let pattern = if hex { parse_hex(pattern)? } else { pattern.as_bytes().to_vec() };
+    if pattern.is_empty() {
+        bail!("The search pattern is empty");
+    }
+
+    let matches = find_matches(&png, &pattern);
+    if format == OutputFormat::Json {
+        let matches: Vec<Value> = matches
+            .iter()
+            .map(|found| {
+                json!({
+                    "index": found.index,
+                    "chunk_type": found.chunk_type.to_string(),
+                    "offset": found.offset,
+                    "file_offset": found.file_offset,
+                })
+            })
+            .collect();
+        println!("{}", Value::from(matches));
+        return Ok(());
+    }
+    if matches.is_empty() {
+        println!("No matches in {}", file_path.display());
+    }
+    for found in &matches {
+        println!(
+            "{} (chunk {}) at offset {}, file offset {}",
+            found.chunk_type, found.index, found.offset, found.file_offset
+        );
+    }
+    Ok(())
+}
+
+struct GrepMatch<'a> {
+    index: usize,
+    chunk_type: &'a ChunkType,
+    // into the chunk's data
+    offset: usize,
+    file_offset: usize,
+}
+
+// overlapping matches are all reported, a pattern never spans two chunks
+fn find_matches<'a>(png: &'a Png, pattern: &[u8]) -> Vec<GrepMatch<'a>> {
+    let mut matches = Vec::new();
+    for (index, (chunk, chunk_start)) in png.chunks().iter().zip(png.chunk_offsets()).enumerate() {
+        for (offset, window) in chunk.data().windows(pattern.len()).enumerate() {
+            if window == pattern {
+                matches.push(GrepMatch {
+                    index,
+                    chunk_type: chunk.chunk_type(),
+                    offset,
+                    // past the length and type fields
+                    file_offset: chunk_start + 8 + offset,
+                });
+            }
+        }
+    }
+    matches
+}
+
+// hex digits in pairs, spaces between bytes are allowed
+fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("{hex} isn't a sequence of hex bytes");
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| Ok(u8::from_str_radix(&digits[index..index + 2], 16)?))
+        .collect()
+}
+
+fn count_report(png: &Png, chunk_type: Option<&ChunkType>) -> Value {
+    let count = match chunk_type {
+        Some(chunk_type) => png.chunks_of_type(chunk_type).count(),
+        None => png.chunks().len(),
+    };
+    json!({ "chunk_type": chunk_type.map(ChunkType::to_string), "count": count })
+}
+
+// a summary of the image, a metadata chunk that doesn't parse is reported rather than failing
+// sizes read like 1.4 MiB unless `exact_bytes`, the JSON report always has exact counts
+pub fn info(file_path: &Path, exact_bytes: bool, format: OutputFormat) -> Result<()> {
+    let png = load_readonly(file_path)?;
+    match format {
+        OutputFormat::Text => {
+            for line in info_lines(&png, exact_bytes) {
+                println!("{line}");
+            }
+        }
+        OutputFormat::Json => {
+            let mut report = info_report(&png);
+            report["file"] = json!(file_path.display().to_string());
+            println!("{report}");
+        }
+    }
+    Ok(())
+}
+
+// binary units with one decimal, anything under 1 KiB stays in bytes
+fn format_size(bytes: u64, exact_bytes: bool) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if exact_bytes || bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+// how many palette entries the text output shows, JSON always has all of them
+const PALETTE_PREVIEW: usize = 4;
+
+fn info_lines(png: &Png, exact_bytes: bool) -> Vec<String> {
+    let mut lines = vec![
+        format!("Chunks: {}", png.chunk_count()),
+        format!("Size: {}", format_size(png.total_size() as u64, exact_bytes)),
+    ];
+    if let Some(chunk) = png.chunk_by_type("IHDR") {
+        match ImageHeader::from_chunk(chunk) {
+            Ok(header) => {
+                lines.push(format!("Dimensions: {} x {}", header.width, header.height));
+                lines.push(match header.check_color() {
+                    Ok(name) => format!("Color: {name}, {} bits per sample", header.bit_depth),
+                    Err(error) => format!("Color: invalid, {error}"),
+                });
+            }
+            Err(error) => lines.push(format!("Dimensions: invalid, {error}")),
+        }
+    }
+    if let Some(chunk) = png.chunk_by_type("PLTE") {
+        lines.push(match Palette::from_chunk(chunk) {
+            Ok(palette) => {
+                let mut preview = palette.hex_colors(PALETTE_PREVIEW).join(", ");
+                if palette.colors.len() > PALETTE_PREVIEW {
+                    preview.push_str(", ...");
+                }
+                format!("Palette: {} colors ({preview})", palette.colors.len())
+            }
+            Err(error) => format!("Palette: invalid, {error}"),
+        });
+    }
+    if let Some(chunk) = png.chunk_by_type("gAMA") {
+        lines.push(match Gamma::from_chunk(chunk) {
+            Ok(gamma) => format!("Gamma: {gamma}"),
+            Err(error) => format!("Gamma: invalid, {error}"),
+        });
+    }
+    if let Some(chunk) = png.chunk_by_type("acTL") {
+        lines.push(match AnimationControl::from_chunk(chunk) {
+            Ok(control) => format!("Animation: {control}"),
+            Err(error) => format!("Animation: invalid, {error}"),
+        });
+    }
+    if let Some(chunk) = png.chunk_by_type("pHYs") {
+        lines.push(match PhysicalDimensions::from_chunk(chunk) {
+            Ok(phys) => format!("Physical size: {phys}"),
+            Err(error) => format!("Physical size: invalid, {error}"),
+        });
+    }
+    if let Some(chunk) = png.chunk_by_type("tIME") {
+        lines.push(match LastModified::from_chunk(chunk) {
+            Ok(time) => format!("Last modified: {time}"),
+            Err(error) => format!("Last modified: invalid, {error}"),
+        });
+    }
+    lines
+}
+
+fn info_report(png: &Png) -> Value {
+    let mut report = json!({ "chunks": png.chunk_count(), "size": png.total_size() });
+    if let Some(chunk) = png.chunk_by_type("IHDR") {
+        report["dimensions"] = match ImageHeader::from_chunk(chunk) {
+            Ok(header) => {
+                report["color"] = match header.check_color() {
+                    Ok(name) => json!({
+                        "color_type": header.color_type,
+                        "name": name,
+                        "bit_depth": header.bit_depth,
+                        "channels": header.channels(),
+                    }),
+                    Err(error) => json!({ "error": error.to_string() }),
+                };
+                json!({ "width": header.width, "height": header.height })
+            }
+            Err(error) => json!({ "error": error.to_string() }),
+        };
+    }
+    if let Some(chunk) = png.chunk_by_type("PLTE") {
+        report["palette"] = match Palette::from_chunk(chunk) {
+            Ok(palette) => json!({
+                "entries": palette.colors.len(),
+                "colors": palette.hex_colors(palette.colors.len()),
+            }),
+            Err(error) => json!({ "error": error.to_string() }),
+        };
+    }
+    if let Some(chunk) = png.chunk_by_type("gAMA") {
+        report["gamma"] = match Gamma::from_chunk(chunk) {
+            Ok(gamma) => json!(gamma.value()),
+            Err(error) => json!({ "error": error.to_string() }),
+        };
+    }
+    if let Some(chunk) = png.chunk_by_type("acTL") {
+        report["animation"] = match AnimationControl::from_chunk(chunk) {
+            Ok(control) => json!({ "frames": control.num_frames, "plays": control.num_plays }),
+            Err(error) => json!({ "error": error.to_string() }),
+        };
+    }
+    if let Some(chunk) = png.chunk_by_type("pHYs") {
+        report["physical"] = match PhysicalDimensions::from_chunk(chunk) {
+            Ok(phys) => json!({
+                "pixels_per_unit_x": phys.pixels_per_unit_x,
+                "pixels_per_unit_y": phys.pixels_per_unit_y,
+                "unit": phys.unit,
+                "dpi": phys.dpi().map(|(x, y)| [x, y]),
+            }),
+            Err(error) => json!({ "error": error.to_string() }),
+        };
+    }
+    if let Some(chunk) = png.chunk_by_type("tIME") {
+        report["last_modified"] = match LastModified::from_chunk(chunk) {
+            Ok(time) => json!(time.to_string()),
+            Err(error) => json!({ "error": error.to_string() }),
+        };
+    }
+    report
+}
+
+pub fn list_types(file_path: &Path, format: OutputFormat) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    if format == OutputFormat::Json {
+        let types: Vec<Value> = distinct_types(&png)
+            .into_iter()
+            .map(|chunk_type| {
+                json!({ "type": chunk_type.to_string(), "description": chunk_type.description() })
+            })
+            .collect();
+        println!("{}", Value::from(types));
+        return Ok(());
+    }
+    for line in describe_types(&png) {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+// what validating one file found, errors are only collected with strict checking
+#[derive(Debug, PartialEq)]
+pub struct ValidateResult {
+    pub path: PathBuf,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+pub fn validate_file(path: &Path, strict: bool) -> Result<ValidateResult> {
+    let png = load_readonly(path)?;
+    Ok(ValidateResult {
+        path: path.to_path_buf(),
+        warnings: validation_warnings(&png),
+        errors: if strict { ordering_violations(&png) } else { Vec::new() },
+    })
+}
+
+// only reports problems unless strict, then a file breaking the chunk ordering rules fails
+pub fn validate(file_paths: &[PathBuf], strict: bool, format: OutputFormat) -> Result<()> {
+    let file_paths = &expand_paths(file_paths)?;
+    let batch = file_paths.len() > 1;
+    let mut reports = Vec::new();
+    let mut failed = 0;
+
+    let results = run_batch(file_paths, |path| validate_file(path, strict));
+    for (path, result) in file_paths.iter().zip(results) {
+        let result = match result {
+            Ok(result) => result,
+            Err(error) if !batch => return Err(error),
+            Err(error) => {
+                failed += 1;
+                reports.extend(batch_failure(path, &error, format));
+                continue;
+            }
+        };
+        if !result.errors.is_empty() {
+            failed += 1;
+        }
+        if format == OutputFormat::Json {
+            reports.push(json!({
+                "file": result.path.display().to_string(),
+                "ok": result.warnings.is_empty() && result.errors.is_empty(),
+                "warnings": result.warnings,
+                "errors": result.errors,
+            }));
+            continue;
+        }
+        if result.warnings.is_empty() && result.errors.is_empty() {
+            println!("{}: no problems found", path.display());
+        }
+        let prefix = if batch { format!("{}: ", path.display()) } else { String::new() };
+        for error in result.errors {
+            println!("{prefix}error: {error}");
+        }
+        for warning in result.warnings {
+            println!("{prefix}warning: {warning}");
+        }
+    }
+    if format == OutputFormat::Json {
+        println!("{}", batch_json(reports));
+    }
+    batch_outcome(failed, file_paths.len())
+}
+
+fn ordering_violations(png: &Png) -> Vec<String> {
+    png.validate().ordering.iter().map(ToString::to_string).collect()
+}
+
+// a path with *, ? or [ in it is expanded as a glob pattern, unless a file by that exact
+// name exists. A pattern has to match at least one file
+pub fn expand_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        let pattern = path.to_string_lossy();
+        if path.exists() || is_url(path) || !pattern.contains(['*', '?', '[']) {
+            expanded.push(path.clone());
+            continue;
+        }
+        let matches = glob::glob(&pattern)
+            .with_context(|| format!("Invalid file pattern {pattern}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Failed to expand {pattern}"))?;
+        if matches.is_empty() {
+            bail!("No files match {pattern}");
+        }
+        expanded.extend(matches);
+    }
+    Ok(expanded)
+}
+
+fn is_url(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// read-only commands also take an http(s) URL in place of a path
+fn load_readonly(path: &Path) -> Result<Png> {
+    if !is_url(path) {
+        return Png::from_file_mapped(path);
+    }
+    #[cfg(feature = "net")]
+    {
+        Png::from_url(&path.to_string_lossy())
+    }
+    #[cfg(not(feature = "net"))]
+    bail!("Loading {} needs pngme built with the net feature", path.display())
+}
+
+// runs `run` on each path on rayon's thread pool, the results keep the order of the paths
+fn run_batch<T, F>(paths: &[PathBuf], run: F) -> Vec<Result<T>>
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T> + Sync,
+{
+    // a single file has nothing to count
+    let bar = if paths.len() > 1 { progress::files(paths.len()) } else { ProgressBar::hidden() };
+    let results = run_batch_with(paths, &bar, run);
+    bar.finish_and_clear();
+    results
+}
+
+fn run_batch_with<T, F>(paths: &[PathBuf], bar: &ProgressBar, run: F) -> Vec<Result<T>>
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T> + Sync,
+{
+    paths
+        .par_iter()
+        .map(|path| {
+            let result = run(path);
+            bar.inc(1);
+            result
+        })
+        .collect()
+}
+
+// printed straight away as text, collected with the other reports under --json
+fn batch_failure(path: &Path, error: &anyhow::Error, format: OutputFormat) -> Option<Value> {
+    match format {
+        OutputFormat::Text => {
+            eprintln!("error: {}: {error:#}", path.display());
+            None
+        }
+        OutputFormat::Json => {
+            Some(json!({ "file": path.display().to_string(), "error": format!("{error:#}") }))
+        }
+    }
+}
+
+// a single file keeps its report as is, several become an array
+fn batch_json(mut reports: Vec<Value>) -> Value {
+    match reports.len() {
+        1 => reports.remove(0),
+        _ => Value::from(reports),
+    }
+}
+
+fn batch_outcome(failed: usize, total: usize) -> Result<()> {
+    if failed > 0 {
+        bail!("{failed} of {total} files failed");
+    }
+    Ok(())
+}
+
+fn validation_warnings(png: &Png) -> Vec<String> {
+    let report = png.validate();
+    let mut warnings: Vec<String> = report
+        .duplicate_unique
+        .iter()
+        .map(|(chunk_type, count)| {
+            format!("{chunk_type} appears {count} times but may only appear once")
+        })
+        .collect();
+
+    if let Some(trailing) = report.trailing {
+        warnings.push(format!(
+            "{} bytes of trailing data after IEND at offset {}",
+            trailing.length, trailing.offset
+        ));
+    }
+    warnings
+}
+
+pub fn diff(first_path: &Path, second_path: &Path, format: OutputFormat) -> Result<()> {
+    let first = Png::from_file(first_path)?;
+    let second = Png::from_file(second_path)?;
+    let diffs = diff_chunks(&first, &second);
+
+    if format == OutputFormat::Json {
+        let diffs: Vec<Value> = diffs.iter().map(ChunkDiff::to_json).collect();
+        println!("{}", json!({ "differences": diffs }));
+        return Ok(());
+    }
+    if diffs.is_empty() {
+        println!("No differences");
+        return Ok(());
+    }
+    for chunk_diff in &diffs {
+        println!("{}", chunk_diff.describe());
+    }
+
+    let count = |predicate: fn(&ChunkDiff) -> bool| diffs.iter().filter(|d| predicate(d)).count();
+    println!(
+        "{} added, {} removed, {} changed",
+        count(|d| matches!(d, ChunkDiff::Added(..))),
+        count(|d| matches!(d, ChunkDiff::Removed(..))),
+        count(|d| matches!(d, ChunkDiff::Changed(..))),
+    );
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum ChunkDiff<'a> {
+    Added(usize, &'a Chunk),
+    Removed(usize, &'a Chunk),
+    Changed(usize, &'a Chunk, &'a Chunk),
+}
+
+impl ChunkDiff<'_> {
+    fn describe(&self) -> String {
+        let summary = |chunk: &Chunk| {
+            format!("{} (length {}, crc {})", chunk.chunk_type(), chunk.length(), chunk.crc())
+        };
+        match self {
+            ChunkDiff::Added(index, chunk) => format!("+ chunk {index}: {}", summary(chunk)),
+            ChunkDiff::Removed(index, chunk) => format!("- chunk {index}: {}", summary(chunk)),
+            ChunkDiff::Changed(index, old, new) => {
+                format!("~ chunk {index}: {} -> {}", summary(old), summary(new))
+            }
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let summary = |chunk: &Chunk| {
+            json!({
+                "type": chunk.chunk_type().to_string(),
+                "length": chunk.length(),
+                "crc": chunk.crc(),
+            })
+        };
+        match self {
+            ChunkDiff::Added(index, chunk) => {
+                json!({ "change": "added", "index": index, "chunk": summary(chunk) })
+            }
+            ChunkDiff::Removed(index, chunk) => {
+                json!({ "change": "removed", "index": index, "chunk": summary(chunk) })
+            }
+            ChunkDiff::Changed(index, old, new) => {
+                json!({ "change": "changed", "index": index, "old": summary(old), "new": summary(new) })
+            }
+        }
+    }
+}
+
+// chunks are lined up by index, so anything past the shorter file is added/removed
+fn diff_chunks<'a>(first: &'a Png, second: &'a Png) -> Vec<ChunkDiff<'a>> {
+    let total = first.chunks().len().max(second.chunks().len());
+    (0..total)
+        .filter_map(|index| match (first.chunks().get(index), second.chunks().get(index)) {
+            (Some(old), Some(new)) if old != new => Some(ChunkDiff::Changed(index, old, new)),
+            (Some(old), None) => Some(ChunkDiff::Removed(index, old)),
+            (None, Some(new)) => Some(ChunkDiff::Added(index, new)),
+            _ => None,
+        })
+        .collect()
+}
+
+// copies chunks of one type, or every ancillary chunk, into the destination before its IEND.
+// The source can also be a JSON file from export or split
+pub fn merge(
+    source_path: &Path,
+    destination_path: &Path,
+    chunk_type: Option<&str>,
+    write: &WriteArgs,
+) -> Result<()> {
+    let source = load_chunk_source(source_path)?;
+    let mut destination = Png::from_file(destination_path)?;
+    let chunk_type = chunk_type.map(ChunkType::from_str).transpose()?;
+
+    let copied = merge_chunks(&source, &mut destination, chunk_type.as_ref());
+    write_png(&destination, destination_path, write)?;
+    let message = format!(
+        "Copied {copied} chunks from {} into {}",
+        source_path.display(),
+        destination_path.display()
+    );
+    report(write, "merge", destination_path, message);
+    Ok(())
+}
+
+// IHDR and IEND are never copied, the destination already has its own
+fn merge_chunks(source: &Png, destination: &mut Png, chunk_type: Option<&ChunkType>) -> usize {
+    let to_copy: Vec<Chunk> = source
+        .chunks()
+        .iter()
+        .filter(|chunk| match chunk_type {
+            Some(chunk_type) => chunk.chunk_type() == chunk_type,
+            None => chunk.chunk_type().is_ancillary(),
+        })
+        .filter(|chunk| ![ChunkType::IHDR, ChunkType::IEND].contains(chunk.chunk_type()))
+        .cloned()
+        .collect();
+
+    let copied = to_copy.len();
+    for chunk in to_copy {
+        destination.insert_before_iend(chunk);
+    }
+    copied
+}
+
+// encode --append-file: the source's ancillary chunks go in before IEND, except ones the
+// destination already has an identical copy of. Critical chunks like IDAT stay behind
+fn append_ancillary(source: &Png, destination: &mut Png) -> usize {
+    let mut appended = 0;
+    for chunk in source.chunks().iter().filter(|chunk| chunk.chunk_type().is_ancillary()) {
+        if !destination.chunks().contains(chunk) {
+            destination.insert_before_iend(chunk.clone());
+            appended += 1;
+        }
+    }
+    appended
+}
+
+// encode with --append-file and no message, only the chunks are added
+pub fn encode_append_only(
+    file_path: &Path,
+    source_path: &Path,
+    output: Option<&Path>,
+    write: &WriteArgs,
+) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let appended = append_ancillary(&Png::from_file(source_path)?, &mut png);
+
+    let output = output_path(file_path, output, write)?;
+    write_png(&png, output, write)?;
+    let message = format!(
+        "Appended {appended} chunks from {} into {}",
+        source_path.display(),
+        output.display()
+    );
+    let fields = [("appended", appended.to_string()), ("chunks", png.chunk_count().to_string())];
+    report_with_fields(write, "encode", output, message, "appended", &fields);
+    Ok(())
+}
+
+// leaves the file alone when there's nothing after IEND
+pub fn strip_trailing(file_path: &Path, write: &WriteArgs) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let trailing = png.strip_trailing();
+    if trailing.is_empty() {
+        let message = format!("No trailing data in {}", file_path.display());
+        report(write, "strip-trailing", file_path, message);
+        return Ok(());
+    }
+
+    write_png(&png, file_path, write)?;
+    let message =
+        format!("Removed {} bytes of trailing data from {}", trailing.len(), file_path.display());
+    report(write, "strip-trailing", file_path, message);
+    Ok(())
+}
+
+// for files some tool left without a terminator, one that has an IEND is left alone
+pub fn fix_end(file_path: &Path, write: &WriteArgs) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    if !png.ensure_iend() {
+        report(write, "fix-end", file_path, format!("{} already has IEND", file_path.display()));
+        return Ok(());
+    }
+
+    write_png(&png, file_path, write)?;
+    report(write, "fix-end", file_path, format!("Added a missing IEND to {}", file_path.display()));
+    Ok(())
+}
+
+// the file is parsed once up front, nothing is written until the save command
+pub fn repl(file_path: &Path, write: &WriteArgs) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    let mut session = Repl::new(file_path, png);
+    session.run(io::stdin().lock(), &mut io::stdout(), write)
+}
+
+// only one tIME chunk is allowed, so any existing ones are replaced
+pub fn touch(file_path: &Path, write: &WriteArgs) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let time = LastModified::now()?;
+    while png.remove_first_of_type(&ChunkType::TIME).is_some() {}
+    png.insert_before_iend(time.to_chunk());
+
+    write_png(&png, file_path, write)?;
+    report(write, "touch", file_path, format!("Set tIME of {} to {time}", file_path.display()));
+    Ok(())
+}
+
+// one tEXt chunk per keyword: an existing one is rewritten where it is and any repeats of
+// the keyword are dropped, otherwise a new chunk goes before IEND
+pub fn replace_text(file_path: &Path, keyword: &str, text: &str, write: &WriteArgs) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let entry = TextEntry::new(keyword, text)?;
+    let replaced = png.replace_or_insert(entry.to_chunk(), |chunk| {
+        TextEntry::from_chunk(chunk).is_ok_and(|existing| existing.keyword == keyword)
+    });
+
+    write_png(&png, file_path, write)?;
+    let action = if replaced { "Replaced" } else { "Added" };
+    let message = format!("{action} tEXt {keyword} in {}", file_path.display());
+    report(write, "replace-text", file_path, message);
+    Ok(())
+}
+
+// leaves the file alone when there are no duplicates
+pub fn dedup(file_path: &Path, include_idat: bool, write: &WriteArgs) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let removed = png.dedup(include_idat);
+    if removed.is_empty() {
+        report(write, "dedup", file_path, format!("No duplicate chunks in {}", file_path.display()));
+        return Ok(());
+    }
+
+    write_png(&png, file_path, write)?;
+    let message =
+        format!("Removed {} duplicate chunks from {}", removed.len(), file_path.display());
+    report(write, "dedup", file_path, message);
+    Ok(())
+}
+
+pub fn rename_type(file_path: &Path, from: &str, to: &str, write: &WriteArgs) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let from = ChunkType::from_str(from)?;
+    let to = ChunkType::from_str(to).context("Invalid target chunk type")?;
+
+    let renamed = png.rename_type(&from, &to);
+    if renamed == 0 {
+        bail!(PngError::ChunkNotFound(from.to_string()));
+    }
+    write_png(&png, file_path, write)?;
+    let message = format!("Renamed {renamed} {from} chunks to {to} in {}", file_path.display());
+    report(write, "rename-type", file_path, message);
+    Ok(())
+}
+
+// IHDR has to stay first and IEND last, whichever chunk is moved, unless `allow_ends`
+pub fn reorder(
+    file_path: &Path,
+    chunk_type: &str,
+    to_index: usize,
+    allow_ends: bool,
+    write: &WriteArgs,
+) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    let ends = |png: &Png| {
+        let chunks = png.chunks();
+        (
+            chunks.first().map(Chunk::chunk_type) == Some(&ChunkType::IHDR),
+            chunks.last().map(Chunk::chunk_type) == Some(&ChunkType::IEND),
+        )
+    };
+    let (ihdr_first, iend_last) = ends(&png);
+
+    let from = png.move_chunk(&chunk_type, to_index)?;
+    let (still_first, still_last) = ends(&png);
+    if !allow_ends && ihdr_first && !still_first {
+        bail!("Moving {chunk_type} to {to_index} would take IHDR off index 0, pass --allow-ends to do it anyway");
+    }
+    if !allow_ends && iend_last && !still_last {
+        bail!("Moving {chunk_type} to {to_index} would leave IEND before the end, pass --allow-ends to do it anyway");
+    }
+    write_png(&png, file_path, write)?;
+    let message =
+        format!("Moved {chunk_type} from index {from} to {to_index} in {}", file_path.display());
+    report(write, "reorder", file_path, message);
+    Ok(())
+}
+
+// only the bits that were asked for change, the rest keep their current case
+pub fn set_bits(
+    file_path: &Path,
+    chunk_type: &str,
+    ancillary: Option<bool>,
+    safe_to_copy: Option<bool>,
+    write: &WriteArgs,
+) -> Result<()> {
+    let mut png = Png::from_file(file_path)?;
+    let from = ChunkType::from_str(chunk_type)?;
+    let mut to = from.clone();
+    if let Some(ancillary) = ancillary {
+        to = to.with_critical(!ancillary);
+    }
+    if let Some(safe_to_copy) = safe_to_copy {
+        to = to.with_safe_to_copy(safe_to_copy);
+    }
+
+    if to == from {
+        let message = format!("{from} already has the requested bits, nothing to do");
+        report(write, "set-bits", file_path, message);
+        return Ok(());
+    }
+    let renamed = png.rename_type(&from, &to);
+    if renamed == 0 {
+        bail!(PngError::ChunkNotFound(from.to_string()));
+    }
+    write_png(&png, file_path, write)?;
+    let message = format!("Changed {renamed} {from} chunks to {to} in {}", file_path.display());
+    report(write, "set-bits", file_path, message);
+    Ok(())
+}
+
+pub fn hash(file_path: &Path, normalize: bool, format: OutputFormat) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    let digest = png_hash(&png, normalize);
+    match format {
+        OutputFormat::Text => println!("{digest}  {}", file_path.display()),
+        OutputFormat::Json => println!(
+            "{}",
+            json!({
+                "file": file_path.display().to_string(),
+                "sha256": digest,
+                "normalized": normalize,
+            })
+        ),
+    }
+    Ok(())
+}
+
+// lists every chunk, good or bad, so the file is parsed leniently. Fails at the end when
+// any CRC doesn't match, after the whole table is out
+pub fn checksum_report(file_path: &Path, format: OutputFormat) -> Result<()> {
+    let png = Png::from_file_lenient(file_path)?;
+    let rows = checksum_rows(&png);
+    match format {
+        OutputFormat::Text => {
+            println!("#\tType\tStored\t\tRecomputed\tMatch");
+            for (index, (chunk, recomputed)) in rows.iter().enumerate() {
+                let matches = if chunk.crc() == *recomputed { "ok" } else { "MISMATCH" };
+                println!(
+                    "{}\t{}\t{:08x}\t{recomputed:08x}\t{matches}",
+                    index + 1,
+                    chunk.chunk_type(),
+                    chunk.crc()
+                );
+            }
+        }
+        OutputFormat::Json => {
+            let rows: Vec<Value> = rows
+                .iter()
+                .enumerate()
+                .map(|(index, (chunk, recomputed))| {
+                    json!({
+                        "index": index,
+                        "type": chunk.chunk_type().to_string(),
+                        "stored": chunk.crc(),
+                        "recomputed": recomputed,
+                        "match": chunk.crc() == *recomputed,
+                    })
+                })
+                .collect();
+            println!("{}", json!({ "file": file_path.display().to_string(), "chunks": rows }));
+        }
+    }
+    let mismatches = rows.iter().filter(|(chunk, recomputed)| chunk.crc() != *recomputed).count();
+    if mismatches > 0 {
+        bail!("{mismatches} of {} chunks have a CRC that doesn't match", rows.len());
+    }
+    Ok(())
+}
+
+// each chunk with the CRC its type and data should have
+fn checksum_rows(png: &Png) -> Vec<(&Chunk, u32)> {
+    png.chunks()
+        .iter()
+        .map(|chunk| (chunk, CrcKind::default().checksum(&chunk.chunk_type().bytes(), chunk.data())))
+        .collect()
+}
+
+// SHA-256 of the file as pngme would write it. Normalized hashes stop at the last chunk, so
+// files that only differ in what follows IEND hash the same
+fn png_hash(png: &Png, normalize: bool) -> String {
+    let mut hasher = Sha256::new();
+    if normalize {
+        hasher.update(png.header());
+        for chunk in png.chunks() {
+            hasher.update(chunk.as_bytes());
+        }
+    } else {
+        hasher.update(png.as_bytes());
+    }
+    hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn capacity(file_path: &Path, chunk_type: &str, format: OutputFormat) -> Result<()> {
+    let png = Png::from_file(file_path)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+    let report = CapacityReport::new(&png, &chunk_type);
+
+    if format == OutputFormat::Json {
+        let report = json!({
+            "chunk_type": chunk_type.to_string(),
+            "max_length": Chunk::MAX_LENGTH,
+            "chunks": report.chunks,
+            "used": report.used,
+            "largest": report.largest,
+            "near_limit": report.largest >= CapacityReport::NEAR_LIMIT,
+        });
+        println!("{report}");
+        return Ok(());
+    }
+
+    println!("Maximum message size per {chunk_type} chunk: {} bytes", Chunk::MAX_LENGTH);
+    println!(
+        "Currently embedded under {chunk_type}: {} bytes in {} chunks",
+        report.used, report.chunks
+    );
+    if report.largest >= CapacityReport::NEAR_LIMIT {
+        println!(
+            "warning: a {chunk_type} chunk holds {} bytes, close to the {} byte limit",
+            report.largest,
+            Chunk::MAX_LENGTH
+        );
+    }
+    Ok(())
+}
+
+// one message bit in the lowest bit of each sample, the first bytes hold the message length
+pub fn lsb_capacity(file_path: &Path, format: OutputFormat) -> Result<()> {
+    let report = lsb_capacity_report(&Png::from_file(file_path)?)?;
+    match format {
+        OutputFormat::Json => println!("{report}"),
+        OutputFormat::Text => {
+            println!(
+                "{} x {} pixels, {} samples each at {} bits",
+                report["width"],
+                report["height"],
+                report["channels"],
+                report["bit_depth"]
+            );
+            println!("Decompressed pixel data: {} bytes", report["pixel_data"]);
+            println!(
+                "LSB capacity: {} bytes ({} samples, {LSB_LENGTH_HEADER} byte length header)",
+                report["capacity"],
+                report["samples"]
+            );
+        }
+    }
+    Ok(())
+}
+
+fn lsb_capacity_report(png: &Png) -> Result<Value> {
+    let header = png
+        .chunk_by_type("IHDR")
+        .ok_or_else(|| PngError::ChunkNotFound("IHDR".to_string()))?;
+    let header = ImageHeader::from_chunk(header)?;
+    let Some(samples) = header.samples() else {
+        bail!("IHDR color type {} isn't defined by the PNG spec", header.color_type);
+    };
+
+    let pixel_data = inflate_idat(png, &header)?;
+    if let Some(expected) = header.filtered_length().filter(|&length| length != pixel_data) {
+        warn!("IDAT inflates to {pixel_data} bytes, IHDR implies {expected}");
+    }
+    Ok(json!({
+        "width": header.width,
+        "height": header.height,
+        "channels": header.channels(),
+        "bit_depth": header.bit_depth,
+        "samples": samples,
+        "pixel_data": pixel_data,
+        "capacity": lsb_capacity_bytes(samples),
+    }))
+}
+
+const LSB_LENGTH_HEADER: u64 = 4;
+
+fn lsb_capacity_bytes(samples: u64) -> u64 {
+    (samples / 8).saturating_sub(LSB_LENGTH_HEADER)
+}
+
+// the length of the inflated IDAT stream. Reading stops a byte past what IHDR implies, or
+// for an interlaced image past what any image of its size could hold, so a corrupt stream
+// can't inflate without bound
+fn inflate_idat(png: &Png, header: &ImageHeader) -> Result<u64> {
+    let compressed: Vec<u8> = png
+        .chunks_of_type(&ChunkType::IDAT)
+        .flat_map(|chunk| chunk.data().iter().copied())
+        .collect();
+    if compressed.is_empty() {
+        bail!(PngError::ChunkNotFound("IDAT".to_string()));
+    }
+    let expected = header.filtered_length().unwrap_or_else(|| {
+        // 2 bytes a sample at most, and the 7 passes add fewer than 2 filter bytes a row
+        header.samples().unwrap_or_default() * 2 + u64::from(header.height) * 2 + 7
+    });
+    io::copy(&mut ZlibDecoder::new(compressed.as_slice()).take(expected + 1), &mut io::sink())
+        .context("IDAT data is not a valid zlib stream")
+}
+
+#[derive(Debug, PartialEq)]
+struct CapacityReport {
+    chunks: usize,
+    used: u64,
+    largest: u32,
+}
+
+impl CapacityReport {
+    // 90% of the chunk length cap
+    const NEAR_LIMIT: u32 = Chunk::MAX_LENGTH / 10 * 9;
+
+    fn new(png: &Png, chunk_type: &ChunkType) -> CapacityReport {
+        png.chunks_of_type(chunk_type).fold(
+            CapacityReport { chunks: 0, used: 0, largest: 0 },
+            |report, chunk| CapacityReport {
+                chunks: report.chunks + 1,
+                used: report.used + u64::from(chunk.length()),
+                largest: report.largest.max(chunk.length()),
+            },
+        )
+    }
+}
+
+// writes back to the input file unless an output path is given
+pub fn strip(file_path: &Path, output: Option<&Path>, write: &WriteArgs) -> Result<()> {
+    let output = output_path(file_path, output, write)?;
+    let (chunks, bytes) = strip_file(file_path, output, write)?;
+    let message =
+        format!("Removed {chunks} ancillary chunks ({bytes} bytes), wrote {}", output.display());
+    report(write, "strip", output, message);
+    Ok(())
+}
+
+// returns how many chunks and bytes were removed
+fn strip_file(file_path: &Path, output: &Path, write: &WriteArgs) -> Result<(usize, usize)> {
+    let mut png = Png::from_file(file_path)?;
+    let removed = png.strip_ancillary();
+    let removed_bytes = removed.iter().map(|chunk| chunk.as_bytes().len()).sum();
+    write_png(&png, output, write)?;
+    Ok((removed.len(), removed_bytes))
+}
+
+// strips every .png file under the directory in place, printing one line per file at the
+// end. A file that fails doesn't stop the others
+// with `summary` a tally of the whole run goes to stderr at the end, as an object under --json
+pub fn strip_recursive(directory: &Path, summary: bool, write: &WriteArgs) -> Result<()> {
+    let mut paths = find_pngs(directory)?;
+    // copies from an earlier --suffix run would otherwise get a second suffix
+    if let Some(suffix) = &write.suffix {
+        paths.retain(|path| {
+            !path.file_stem().is_some_and(|stem| stem.to_string_lossy().ends_with(suffix.as_str()))
+        });
+    }
+    let bar = progress::files(paths.len());
+    let results: Vec<_> = paths
+        .iter()
+        .map(|path| {
+            bar.set_message(path.display().to_string());
+            let result = strip_file(path, path, write);
+            bar.inc(1);
+            result
+        })
+        .collect();
+    bar.finish_and_clear();
+
+    let mut reports = Vec::new();
+    let mut failed = 0;
+    let mut total = (0, 0);
+    let mut tally = BatchSummary::default();
+    for (path, result) in paths.iter().zip(results) {
+        tally.record(&result, |&(chunks, _)| chunks > 0);
+        let (chunks, bytes) = match result {
+            Ok(removed) => removed,
+            Err(error) if is_not_png(&error) => {
+                warn!("Skipping {}, it isn't a PNG file", path.display());
+                continue;
+            }
+            Err(error) => {
+                failed += 1;
+                reports.extend(batch_failure(path, &error, write.format));
+                continue;
+            }
+        };
+        total = (total.0 + chunks, total.1 + bytes);
+        match write.format {
+            OutputFormat::Json => reports.push(json!({
+                "file": path.display().to_string(),
+                "removed_chunks": chunks,
+                "removed_bytes": bytes,
+            })),
+            OutputFormat::Text => {
+                println!("{}: removed {chunks} ancillary chunks ({bytes} bytes)", path.display())
+            }
+        }
+    }
+    match write.format {
+        OutputFormat::Json => println!("{}", Value::from(reports)),
+        OutputFormat::Text => println!(
+            "Stripped {} of {} files, removed {} chunks ({} bytes) in total",
+            paths.len() - failed - tally.skipped,
+            paths.len(),
+            total.0,
+            total.1
+        ),
+    }
+    if summary {
+        match write.format {
+            OutputFormat::Text => eprintln!("{tally}"),
+            OutputFormat::Json => eprintln!("{}", tally.to_json()),
+        }
+    }
+    batch_outcome(failed, paths.len())
+}
+
+// per file outcomes of a batch run. A file that isn't a PNG at all is counted as skipped
+// rather than as an error and doesn't fail the run
+#[derive(Debug, Default, PartialEq, Eq)]
+struct BatchSummary {
+    processed: usize,
+    modified: usize,
+    skipped: usize,
+    errors: usize,
+}
+
+impl BatchSummary {
+    fn record<T>(&mut self, result: &Result<T>, modified: impl Fn(&T) -> bool) {
+        self.processed += 1;
+        match result {
+            Ok(outcome) if modified(outcome) => self.modified += 1,
+            Ok(_) => {}
+            Err(error) if is_not_png(error) => self.skipped += 1,
+            Err(_) => self.errors += 1,
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "processed": self.processed,
+            "modified": self.modified,
+            "skipped_not_png": self.skipped,
+            "errors": self.errors,
+        })
+    }
+}
+
+impl Display for BatchSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "processed {} files, modified {}, skipped {} (not PNG), errors {}",
+            self.processed, self.modified, self.skipped, self.errors
+        )
+    }
+}
+
+fn is_not_png(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| matches!(cause.downcast_ref(), Some(PngError::IncorrectPNGSignature)))
+}
+
+// every file with a .png extension, case insensitive, in a stable order. Symlinks aren't
+// followed
+fn find_pngs(directory: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in WalkDir::new(directory).follow_links(false).sort_by_file_name() {
+        let entry =
+            entry.with_context(|| format!("Failed to walk {}", directory.display()))?;
+        let is_png = entry
+            .path()
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        if entry.file_type().is_file() && is_png {
+            paths.push(entry.into_path());
+        }
+    }
+    Ok(paths)
+}
+
+// falls back to writing in place when no output is given
+fn output_path<'a>(
+    file_path: &'a Path,
+    output: Option<&'a Path>,
+    write: &WriteArgs,
+) -> Result<&'a Path> {
+    match output {
+        Some(_) if write.suffix.is_some() => bail!("Pass either --output or --suffix, not both"),
+        Some(output) => {
+            check_output(file_path, output, write.force)?;
+            Ok(output)
+        }
+        None => Ok(file_path),
+    }
+}
+
+// an existing output is only replaced with --force, unless it's the input itself
+fn check_output(file_path: &Path, output: &Path, force: bool) -> Result<()> {
+    if !output.exists() || force {
+        return Ok(());
+    }
+    let same_file = matches!(
+        (file_path.canonicalize(), output.canonicalize()),
+        (Ok(input), Ok(output)) if input == output
+    );
+    if !same_file {
+        bail!("Output file {} exists, pass --force to overwrite", output.display());
+    }
+    Ok(())
+}
+
+// prints the usual message, or a small status object under --json. With --suffix the
+// message still names the source, so where it was written goes alongside
+fn report(write: &WriteArgs, command: &str, path: &Path, message: String) {
+    report_with_fields(write, command, path, message, command, &[]);
+}
+
+// commands with more to say under --porcelain pass their own status word and fields
+fn report_with_fields(
+    write: &WriteArgs,
+    command: &str,
+    path: &Path,
+    message: String,
+    status: &str,
+    fields: &[(&str, String)],
+) {
+    if let Some(line) = status_line(write, command, path, message, status, fields) {
+        println!("{line}");
+    }
+}
+
+// None under --quiet-success, failures still come back as errors and are shown by main
+fn status_line(
+    write: &WriteArgs,
+    command: &str,
+    path: &Path,
+    message: String,
+    status: &str,
+    fields: &[(&str, String)],
+) -> Option<String> {
+    if write.quiet_success {
+        return None;
+    }
+    if write.porcelain {
+        return Some(porcelain_line(write, status, path, fields));
+    }
+    let line = match write.format {
+        OutputFormat::Text if write.suffix.is_some() => {
+            format!("{message} (written to {})", write.destination(path).display())
+        }
+        OutputFormat::Text => message,
+        OutputFormat::Json => status_json(write, command, path, &message).to_string(),
+    };
+    Some(line)
+}
+
+fn status_json(write: &WriteArgs, command: &str, path: &Path, message: &str) -> Value {
+    let mut status = json!({
+        "command": command,
+        "file": path.display().to_string(),
+        "status": "ok",
+        "dry_run": write.dry_run,
+        "message": message,
+    });
+    if write.suffix.is_some() {
+        status["output"] = Value::from(write.destination(path).display().to_string());
+    }
+    status
+}
+
+// the fields keep their order and file always comes last, so a path with spaces in it is
+// just the rest of the line
+fn porcelain_line(write: &WriteArgs, status: &str, path: &Path, fields: &[(&str, String)]) -> String {
+    let mut line = status.to_string();
+    for (key, value) in fields {
+        line.push_str(&format!(" {key}={value}"));
+    }
+    if write.dry_run {
+        line.push_str(" dry_run=true");
+    }
+    line.push_str(&format!(" file={}", write.destination(path).display()));
+    line
+}
+
+// every mutating command goes through here
+pub(crate) fn write_png(png: &Png, path: &Path, write: &WriteArgs) -> Result<()> {
+    let original = fs::metadata(path).ok();
+    let path = &write.destination(path);
+    if write.suffix.is_some() && path.exists() && !write.force {
+        bail!("Output file {} exists, pass --force to overwrite", path.display());
+    }
+    // under --json or --porcelain the status carries the dry run flag instead
+    if write.dry_run && (write.format == OutputFormat::Json || write.porcelain) {
+        return Ok(());
+    }
+    if write.dry_run {
+        println!(
+            "Would write {} chunks ({} bytes) to {} (dry run, no file written)",
+            png.chunks().len(),
+            png.total_size(),
+            path.display()
+        );
+        return Ok(());
+    }
+    if let Some(suffix) = &write.backup {
+        backup_file(path, suffix, write.force)?;
+    }
+    let keep = original.as_ref().map(|metadata| (metadata, write.preserve_times));
+    let size = png.total_size() as u64;
+    if size >= progress::LARGE_FILE {
+        let bar = progress::bytes(size);
+        bar.set_message(format!("writing {}", path.display()));
+        write_atomically(path, keep, |writer| png.write_to(&mut bar.wrap_write(writer)))?;
+        bar.finish_and_clear();
+    } else {
+        write_atomically(path, keep, |writer| png.write_to(writer))?;
+    }
+    info!("Wrote {} chunks to {}", png.chunks().len(), path.display());
+    Ok(())
+}
+
+// writes to a temp file in the same directory and renames it over the target, so a
+// failure part way through never leaves a truncated file behind. The temp file is
+// removed on error. With `original` the replacement gets the source file's permissions, and
+// its mtime too when the flag is set, before the rename
+fn write_atomically<F>(
+    path: &Path,
+    original: Option<(&fs::Metadata, bool)>,
+    write_contents: F,
+) -> Result<()>
+where
+    F: FnOnce(&mut BufWriter<&mut File>) -> io::Result<()>,
+{
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut temp_file = NamedTempFile::new_in(directory)
+        .with_context(|| format!("Failed to create a temporary file in {}", directory.display()))?;
+
+    let mut writer = BufWriter::new(temp_file.as_file_mut());
+    write_contents(&mut writer)
+        .and_then(|_| writer.flush())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    drop(writer);
+
+    if let Some((metadata, preserve_times)) = original {
+        copy_attributes(temp_file.path(), metadata, preserve_times);
+    }
+    temp_file
+        .persist(path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+// not being able to copy these isn't worth losing the edit over, so failures only warn
+fn copy_attributes(path: &Path, original: &fs::Metadata, preserve_times: bool) {
+    if let Err(error) = fs::set_permissions(path, original.permissions()) {
+        warn!("Couldn't keep the original permissions: {error}");
+    }
+    if preserve_times {
+        let mtime = filetime::FileTime::from_last_modification_time(original);
+        if let Err(error) = filetime::set_file_mtime(path, mtime) {
+            warn!("Couldn't keep the original modification time: {error}");
+        }
+    }
+}
+
+// copies the file about to be overwritten to <path><suffix>, nothing to do if it doesn't exist yet
+fn backup_file(path: &Path, suffix: &str, force: bool) -> Result<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(suffix);
+    let backup = PathBuf::from(backup);
+
+    if backup.exists() && !force {
+        bail!("Backup file {} already exists, pass --force to overwrite it", backup.display());
+    }
+    fs::copy(path, &backup)
+        .with_context(|| format!("Failed to back up {} to {}", path.display(), backup.display()))?;
+    info!("Backed up {} to {}", path.display(), backup.display());
+    Ok(Some(backup))
+}
+
+// in the order they first appear
+fn distinct_types(png: &Png) -> Vec<&ChunkType> {
+    let mut seen: Vec<&ChunkType> = Vec::new();
+    for chunk in png.chunks() {
+        if !seen.contains(&chunk.chunk_type()) {
+            seen.push(chunk.chunk_type());
+        }
+    }
+    seen
+}
+
+// one line per distinct chunk type
+fn describe_types(png: &Png) -> Vec<String> {
+    distinct_types(png)
+        .iter()
+        .map(|chunk_type| {
+            let description = chunk_type.description().unwrap_or("(non-standard)");
+            format!("{chunk_type} -> {description}")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use std::str::FromStr;
+
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Chunk {
+        let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+        Chunk::new(chunk_type, data.bytes().collect())
+    }
+
+    #[test]
+    fn test_describe_types() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header"),
+            chunk_from_strings("IDAT", "first"),
+            chunk_from_strings("IDAT", "second"),
+            chunk_from_strings("ruSt", "message"),
+            chunk_from_strings("IEND", ""),
+        ]);
+
+        let expected = vec![
+            "IHDR -> Image header",
+            "IDAT -> Image data",
+            "ruSt -> (non-standard)",
+            "IEND -> Image trailer",
+        ];
+        assert_eq!(describe_types(&png), expected);
+    }
+
+    #[test]
+    fn test_validate_batch_returns_every_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = Vec::new();
+        for name in ["a.png", "b.png", "c.png"] {
+            let path = dir.path().join(name);
+            fs::write(&path, Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+            paths.push(path);
+        }
+        let broken = dir.path().join("broken.png");
+        fs::write(&broken, b"not a png").unwrap();
+        paths.insert(1, broken);
+
+        let results = run_batch(&paths, |path| validate_file(path, false));
+
+        assert_eq!(results.len(), 4);
+        assert!(results[1].is_err());
+        for (path, result) in paths.iter().zip(&results).filter(|(_, result)| result.is_ok()) {
+            let result = result.as_ref().unwrap();
+            assert_eq!(&result.path, path);
+            assert!(result.warnings.is_empty());
+        }
+        assert!(validate(&paths, false, OutputFormat::Json).is_err());
+        paths.remove(1);
+        validate(&paths, false, OutputFormat::Json).unwrap();
+    }
+
+    #[test]
+    fn test_batch_progress_counts_every_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut paths = Vec::new();
+        for name in ["a.png", "b.png", "c.png", "broken.png"] {
+            let path = dir.path().join(name);
+            fs::write(&path, Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+            paths.push(path);
+        }
+        fs::write(&paths[3], b"not a png").unwrap();
+
+        let bar = ProgressBar::hidden();
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let results = run_batch_with(&paths, &bar, |path| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Png::from_file(path)
+        });
+
+        // failures are counted too, the bar ends at the number of files either way
+        assert_eq!(results.len(), 4);
+        assert_eq!(calls.into_inner(), 4);
+        assert_eq!(bar.position(), 4);
+    }
+
+    #[test]
+    fn test_expand_paths_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["one.png", "two.png", "notes.txt"] {
+            fs::write(dir.path().join(name), Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+        }
+
+        let pattern = dir.path().join("*.png");
+        let expanded = expand_paths(std::slice::from_ref(&pattern)).unwrap();
+        assert_eq!(expanded, vec![dir.path().join("one.png"), dir.path().join("two.png")]);
+        validate(&[pattern], false, OutputFormat::Text).unwrap();
+
+        let error = expand_paths(&[dir.path().join("*.gif")]).unwrap_err();
+        assert!(error.to_string().starts_with("No files match"));
+    }
+
+    #[test]
+    fn test_expand_paths_keeps_literal_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let literal = dir.path().join("[draft].png");
+        fs::write(&literal, Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+
+        assert_eq!(expand_paths(std::slice::from_ref(&literal)).unwrap(), vec![literal]);
+        let plain = dir.path().join("missing.png");
+        assert_eq!(expand_paths(std::slice::from_ref(&plain)).unwrap(), vec![plain]);
+    }
+
+    #[test]
+    fn test_strip_recursive_visits_each_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let deeper = dir.path().join("sub").join("deeper");
+        fs::create_dir_all(&deeper).unwrap();
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("tEXt", "Comment\0strip me"));
+        let expected = vec![
+            dir.path().join("a.png"),
+            dir.path().join("sub").join("B.PNG"),
+            deeper.join("c.png"),
+        ];
+        for path in &expected {
+            fs::write(path, png.as_bytes()).unwrap();
+        }
+        fs::write(dir.path().join("sub").join("notes.txt"), b"not an image").unwrap();
+
+        assert_eq!(find_pngs(dir.path()).unwrap(), expected);
+        strip_recursive(dir.path(), false, &WriteArgs::default()).unwrap();
+
+        for path in &expected {
+            let stripped = Png::from_file(path).unwrap();
+            assert!(stripped.chunk_by_type("tEXt").is_none(), "{} not stripped", path.display());
+        }
+        assert_eq!(fs::read(dir.path().join("sub").join("notes.txt")).unwrap(), b"not an image");
+    }
+
+    #[test]
+    fn test_batch_summary_tallies_outcomes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("tEXt", "Comment\0strip me"));
+        for name in ["a.png", "b.png"] {
+            fs::write(dir.path().join(name), png.as_bytes()).unwrap();
+        }
+        fs::write(dir.path().join("c.png"), Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+        fs::write(dir.path().join("d.png"), b"a text file in disguise").unwrap();
+        let truncated = &png.as_bytes()[..20];
+        fs::write(dir.path().join("e.png"), truncated).unwrap();
+
+        let mut tally = BatchSummary::default();
+        for path in find_pngs(dir.path()).unwrap() {
+            tally.record(&strip_file(&path, &path, &WriteArgs::default()), |&(chunks, _)| chunks > 0);
+        }
+        assert_eq!(tally, BatchSummary { processed: 5, modified: 2, skipped: 1, errors: 1 });
+        assert_eq!(tally.to_string(), "processed 5 files, modified 2, skipped 1 (not PNG), errors 1");
+        assert_eq!(tally.to_json()["skipped_not_png"], 1);
+
+        // only the truncated file fails the run, the impostor is just skipped
+        let error = strip_recursive(dir.path(), true, &WriteArgs::default()).unwrap_err();
+        assert_eq!(error.to_string(), "1 of 5 files failed");
+        fs::remove_file(dir.path().join("e.png")).unwrap();
+        strip_recursive(dir.path(), true, &WriteArgs::default()).unwrap();
+    }
+
+    #[test]
+    fn test_render_message_formats() {
+        let payload = b"Hi\xff\x00";
+        let render = |format| render_message(payload, format);
+
+        assert!(render(DecodeFormat::Utf8).is_err());
+        assert_eq!(render(DecodeFormat::Hex).unwrap(), b"4869ff00");
+        assert_eq!(render(DecodeFormat::Base64).unwrap(), b"SGn/AA==");
+        assert_eq!(render(DecodeFormat::Raw).unwrap(), payload);
+        assert_eq!(render_message(b"plain", DecodeFormat::Utf8).unwrap(), b"plain");
+    }
+
+    #[test]
+    fn test_ordering_violations() {
+        assert!(ordering_violations(&Png::from_chunks(testing_chunks())).is_empty());
+
+        let mut chunks = testing_chunks();
+        chunks.insert(0, chunk_from_strings("tEXt", "Comment\0early"));
+        assert_eq!(
+            ordering_violations(&Png::from_chunks(chunks)),
+            vec!["IHDR must be the first chunk but is chunk 2, after tEXt"]
+        );
+
+        let mut chunks = testing_chunks();
+        chunks.remove(0);
+        chunks.push(chunk_from_strings("tEXt", "Comment\0late"));
+        assert_eq!(
+            ordering_violations(&Png::from_chunks(chunks)),
+            vec![
+                "IHDR chunk is missing",
+                "IEND must be the last chunk but is chunk 2 of 3, followed by tEXt"
+            ]
+        );
+
+        assert_eq!(
+            ordering_violations(&Png::from_chunks(Vec::new())),
+            vec!["IHDR chunk is missing", "IEND chunk is missing"]
+        );
+    }
+
+    #[test]
+    fn test_validate_strict_fails_on_misplaced_ihdr() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("early.png");
+        let mut chunks = testing_chunks();
+        chunks.insert(0, chunk_from_strings("tEXt", "Comment\0early"));
+        fs::write(&path, Png::from_chunks(chunks).as_bytes()).unwrap();
+
+        let result = validate_file(&path, true).unwrap();
+        assert_eq!(result.errors.len(), 1);
+        assert!(validate(std::slice::from_ref(&path), true, OutputFormat::Text).is_err());
+        validate(&[path], false, OutputFormat::Text).unwrap();
+    }
+
+    #[test]
+    fn test_info_reports_phys() {
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(Chunk::new(chunk_type("pHYs"), vec![0, 0, 0x0b, 0x13, 0, 0, 0x0b, 0x13, 1]));
+        let lines = info_lines(&png, false);
+        assert!(lines.contains(&"Physical size: 2835 x 2835 pixels per metre (72 x 72 DPI)".to_string()));
+        assert_eq!(info_report(&png)["physical"]["unit"], 1);
+
+        png.insert_before_iend(Chunk::new(chunk_type("tIME"), vec![0x07, 0xe8, 2, 29, 13, 5, 9]));
+        assert!(info_lines(&png, false).contains(&"Last modified: 2024-02-29T13:05:09Z".to_string()));
+        assert_eq!(info_report(&png)["last_modified"], "2024-02-29T13:05:09Z");
+
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(Chunk::new(chunk_type("pHYs"), vec![0; 4]));
+        let lines = info_lines(&png, false);
+        assert!(lines.contains(&"Physical size: invalid, pHYs chunk should hold 9 bytes, found 4".to_string()));
+        assert!(lines.contains(&"Dimensions: invalid, IHDR chunk should hold 13 bytes, found 6".to_string()));
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0, false), "0 B");
+        assert_eq!(format_size(1023, false), "1023 B");
+        assert_eq!(format_size(1024, false), "1.0 KiB");
+        assert_eq!(format_size(1536, false), "1.5 KiB");
+        assert_eq!(format_size(1024 * 1024 - 1, false), "1024.0 KiB");
+        assert_eq!(format_size(1024 * 1024, false), "1.0 MiB");
+        assert_eq!(format_size(1_468_006, false), "1.4 MiB");
+        assert_eq!(format_size(5 << 40, false), "5.0 TiB");
+        assert_eq!(format_size(1_468_006, true), "1468006 B");
+
+        let png = Png::from_chunks(testing_chunks());
+        assert!(info_lines(&png, true).contains(&"Size: 54 B".to_string()));
+        assert_eq!(info_report(&png)["size"], 54);
+    }
+
+    #[test]
+    fn test_info_reports_palette() {
+        // a 2 x 1 image with 2 bit indices into a 5 color palette
+        let header = [0, 0, 0, 2, 0, 0, 0, 1, 2, 3, 0, 0, 0];
+        let mut palette = vec![0xff, 0, 0, 0, 0xff, 0, 0, 0, 0xff, 0, 0, 0];
+        palette.extend([0xff; 3]);
+        let png = Png::from_chunks(vec![
+            Chunk::new(ChunkType::IHDR, header.to_vec()),
+            Chunk::new(chunk_type("PLTE"), palette),
+            Chunk::new(ChunkType::IDAT, Vec::new()),
+            Chunk::new(ChunkType::IEND, Vec::new()),
+        ]);
+        let lines = info_lines(&png, false);
+        assert!(lines.contains(&"Color: indexed, 2 bits per sample".to_string()));
+        assert!(lines.contains(&"Palette: 5 colors (#ff0000, #00ff00, #0000ff, #000000, ...)".to_string()));
+        let report = info_report(&png);
+        assert_eq!(report["palette"]["entries"], 5);
+        assert_eq!(report["palette"]["colors"][4], "#ffffff");
+
+        // 16 bit indices aren't a thing
+        let header = [0, 0, 0, 2, 0, 0, 0, 1, 16, 3, 0, 0, 0];
+        let png = Png::from_chunks(vec![Chunk::new(ChunkType::IHDR, header.to_vec())]);
+        let lines = info_lines(&png, false);
+        assert!(lines.iter().any(|line| line.starts_with("Color: invalid, Bit depth 16 isn't allowed")));
+        assert!(info_report(&png)["color"]["error"].is_string());
+
+        let png = Png::from_chunks(vec![Chunk::new(chunk_type("PLTE"), vec![0; 4])]);
+        assert!(info_lines(&png, false).contains(&"Palette: invalid, PLTE length 4 isn't a multiple of 3".to_string()));
+        assert!(info_report(&png)["palette"]["error"].is_string());
+    }
+
+    #[test]
+    fn test_info_reports_gamma() {
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(Chunk::new(chunk_type("gAMA"), 45455u32.to_be_bytes().to_vec()));
+        assert!(info_lines(&png, false).contains(&"Gamma: 0.45455".to_string()));
+        assert_eq!(info_report(&png)["gamma"], 0.45455);
+
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(Chunk::new(chunk_type("gAMA"), vec![0; 2]));
+        assert!(info_lines(&png, false).contains(&"Gamma: invalid, gAMA chunk should hold 4 bytes, found 2".to_string()));
+    }
+
+    #[test]
+    fn test_info_reports_animation() {
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_after_type(&ChunkType::IHDR, Chunk::new(chunk_type("acTL"), vec![0, 0, 0, 2, 0, 0, 0, 0]))
+            .unwrap();
+        png.insert_before_iend(Chunk::new(chunk_type("fcTL"), vec![0; 26]));
+        png.insert_before_iend(Chunk::new(chunk_type("fdAT"), vec![0; 8]));
+
+        assert!(info_lines(&png, false).contains(&"Animation: 2 frames, looping forever".to_string()));
+        assert_eq!(info_report(&png)["animation"], json!({ "frames": 2, "plays": 0 }));
+        // the frame chunks print like any other
+        let mut out = Vec::new();
+        print_to(&mut out, false, &png, &PrintArgs::default()).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("5\tfdAT\t8\n"));
+    }
+
+    #[test]
+    fn test_print_plain_when_not_a_terminal() {
+        let png = Png::from_chunks(testing_chunks());
+        let mut out = Vec::new();
+        print_to(&mut out, false, &png, &PrintArgs::default()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1\tIHDR\t6\n2\tIDAT\t4\n3\tIEND\t0\n");
+
+        let mut out = Vec::new();
+        print_to(&mut out, true, &png, &PrintArgs::default()).unwrap();
+        assert!(String::from_utf8(out).unwrap().starts_with("PNG Image:"));
+    }
+
+    #[test]
+    fn test_print_color() {
+        let png = Png::from_chunks(testing_chunks());
+        let mut plain = Vec::new();
+        print_to(&mut plain, true, &png, &PrintArgs::default()).unwrap();
+        let plain = String::from_utf8(plain).unwrap();
+        assert!(!plain.contains('\x1b'));
+        assert_eq!(plain, png.to_string());
+
+        let options = PrintArgs { color: true, ..PrintArgs::default() };
+        let mut out = Vec::new();
+        print_to(&mut out, true, &png, &options).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("Chunk 2: \x1b[36mIDAT\x1b[39m\n"));
+    }
+
+    #[test]
+    fn test_print_offsets() {
+        // IHDR holds 6 bytes and IDAT 4, so they start at 8, 26 and 42
+        let png = Png::from_chunks(testing_chunks());
+        let options = PrintArgs { offsets: true, ..PrintArgs::default() };
+        let mut out = Vec::new();
+        print_to(&mut out, false, &png, &options).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1\tIHDR\t6\t8\n2\tIDAT\t4\t26\n3\tIEND\t0\t42\n");
+
+        let mut out = Vec::new();
+        print_to(&mut out, true, &png, &options).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("Chunk 2: IDAT (4 B) at offset 26\n"));
+        assert_eq!(print_report(&png, &PrintArgs::default())["chunks"][2]["offset"], 42);
+    }
+
+    #[test]
+    fn test_print_flags() {
+        let png = Png::from_chunks(testing_chunks());
+        let options = PrintArgs { flags: true, ..PrintArgs::default() };
+        let mut out = Vec::new();
+        print_to(&mut out, false, &png, &options).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("1\tIHDR\t6\tcritical, public, reserved-valid, unsafe-to-copy\n"));
+
+        let mut out = Vec::new();
+        print_to(&mut out, true, &png, &options).unwrap();
+        let expected = "Chunk 2: IDAT (4 B) [critical, public, reserved-valid, unsafe-to-copy]\n";
+        assert!(String::from_utf8(out).unwrap().contains(expected));
+    }
+
+    #[test]
+    fn test_print_type_filters() {
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("tEXt", "Title\0one"));
+        png.insert_before_iend(chunk_from_strings("IDAT", "more"));
+        png.insert_before_iend(chunk_from_strings("tEXt", "Author\0two"));
+
+        let options = PrintArgs { types: vec![chunk_type("tEXt")], ..PrintArgs::default() };
+        let mut out = Vec::new();
+        print_to(&mut out, false, &png, &options).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "3\ttEXt\t9\n5\ttEXt\t10\n");
+        let mut out = Vec::new();
+        print_to(&mut out, true, &png, &options).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Number of chunks: 6 (2 shown)"));
+        assert!(!out.contains("IDAT"));
+        let types: Vec<Value> = print_report(&png, &options)["chunks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|chunk| chunk["type"].clone())
+            .collect();
+        assert_eq!(types, ["tEXt", "tEXt"]);
+
+        // include first, then exclude
+        let options = PrintArgs {
+            types: vec![chunk_type("IHDR"), chunk_type("IDAT")],
+            exclude: vec![chunk_type("IDAT")],
+            ..PrintArgs::default()
+        };
+        let mut out = Vec::new();
+        print_to(&mut out, false, &png, &options).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "1\tIHDR\t6\n");
+    }
+
+    #[test]
+    fn test_print_json_is_valid() {
+        let png = Png::from_chunks(testing_chunks());
+        let parsed: Value = serde_json::from_str(&print_report(&png, &PrintArgs::default()).to_string()).unwrap();
+
+        let chunks = parsed["chunks"].as_array().unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1]["type"], "IDAT");
+        assert_eq!(chunks[1]["length"], 4);
+        assert_eq!(chunks[1]["critical"], true);
+        assert_eq!(parsed["trailing_bytes"], 0);
+    }
+
+    #[test]
+    fn test_count_json_is_valid() {
+        let png = Png::from_chunks(testing_chunks());
+
+        let all: Value = serde_json::from_str(&count_report(&png, None).to_string()).unwrap();
+        assert_eq!(all["count"], 3);
+        assert!(all["chunk_type"].is_null());
+
+        let idat = count_report(&png, Some(&chunk_type("IDAT"))).to_string();
+        let idat: Value = serde_json::from_str(&idat).unwrap();
+        assert_eq!(idat["count"], 1);
+        assert_eq!(idat["chunk_type"], "IDAT");
+    }
+
+    #[test]
+    fn test_json_dry_run_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let before = fs::read(&path).unwrap();
+        let write = WriteArgs { dry_run: true, format: OutputFormat::Json, ..WriteArgs::default() };
+
+        strip(&path, None, &write).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), before);
+        assert_eq!(status_json(&write, "strip", &path, "done")["dry_run"], true);
+    }
+
+    #[test]
+    fn test_porcelain_line() {
+        let path = Path::new("dir/my pic.png");
+        let fields = [
+            ("type", "ruSt".to_string()),
+            ("bytes", "58".to_string()),
+            ("chunks", "6".to_string()),
+        ];
+        let write = WriteArgs { porcelain: true, ..WriteArgs::default() };
+        assert_eq!(
+            porcelain_line(&write, "encoded", path, &fields),
+            "encoded type=ruSt bytes=58 chunks=6 file=dir/my pic.png"
+        );
+        let write = WriteArgs { dry_run: true, suffix: Some("_out".to_string()), ..write };
+        assert_eq!(porcelain_line(&write, "touch", path, &[]), "touch dry_run=true file=dir/my pic_out.png");
+
+        // the porcelain dry run prints nothing of its own
+        let dir = tempfile::tempdir().unwrap();
+        let png_path = write_testing_png(dir.path());
+        let before = fs::read(&png_path).unwrap();
+        encode(&png_path, &chunk_type("ruSt"), "secret", &EncodeArgs::default(), None, &write).unwrap();
+        assert_eq!(fs::read(&png_path).unwrap(), before);
+    }
+
+    #[test]
+    fn test_quiet_success() {
+        let path = Path::new("pic.png");
+        let line = |write: &WriteArgs| status_line(write, "encode", path, "Encoded".to_string(), "encoded", &[]);
+        assert_eq!(line(&WriteArgs::default()).as_deref(), Some("Encoded"));
+        for write in [
+            WriteArgs { quiet_success: true, ..WriteArgs::default() },
+            WriteArgs { quiet_success: true, porcelain: true, ..WriteArgs::default() },
+            WriteArgs { quiet_success: true, format: OutputFormat::Json, ..WriteArgs::default() },
+        ] {
+            assert_eq!(line(&write), None);
+        }
+
+        // the write still happens, and a failure is still an error
+        let dir = tempfile::tempdir().unwrap();
+        let png_path = write_testing_png(dir.path());
+        let write = WriteArgs { quiet_success: true, ..WriteArgs::default() };
+        encode(&png_path, &chunk_type("ruSt"), "quiet", &EncodeArgs::default(), None, &write).unwrap();
+        assert!(Png::from_file(&png_path).unwrap().chunk_by_type("ruSt").is_some());
+        let error = remove(&png_path, &chunk_type("noNe"), &write).unwrap_err();
+        assert_eq!(error.to_string(), "Chunk not found: noNe");
+    }
+
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            chunk_from_strings("IHDR", "header"),
+            chunk_from_strings("IDAT", "data"),
+            chunk_from_strings("IEND", ""),
+        ]
+    }
+
+    fn chunk_type(chunk_type: &str) -> ChunkType {
+        ChunkType::from_str(chunk_type).unwrap()
+    }
+
+    fn write_testing_png(dir: &Path) -> PathBuf {
+        let path = dir.join("test.png");
+        fs::write(&path, Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_encode_with_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let original = fs::read(&path).unwrap();
+        let write = WriteArgs { backup: Some(".bak".to_string()), ..Default::default() };
+
+        encode(&path, &chunk_type("ruSt"), "secret", &EncodeArgs::default(), None, &write).unwrap();
+
+        assert_eq!(fs::read(dir.path().join("test.png.bak")).unwrap(), original);
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_backup_refuses_to_overwrite_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let backup_path = dir.path().join("test.png.orig");
+        fs::write(&backup_path, b"older backup").unwrap();
+
+        let mut write = WriteArgs { backup: Some(".orig".to_string()), ..Default::default() };
+        assert!(encode(&path, &chunk_type("ruSt"), "secret", &EncodeArgs::default(), None, &write).is_err());
+        assert_eq!(fs::read(&backup_path).unwrap(), b"older backup");
+
+        write.force = true;
+        let original = fs::read(&path).unwrap();
+        encode(&path, &chunk_type("ruSt"), "secret", &EncodeArgs::default(), None, &write).unwrap();
+        assert_eq!(fs::read(&backup_path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_encode_wraps_only_with_transforms() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let options = EncodeArgs { compress: true, base64: true, ..EncodeArgs::default() };
+        encode(&path, &chunk_type("ruSt"), "packed", &options, None, &WriteArgs::default()).unwrap();
+        encode(&path, &chunk_type("raWw"), "plain", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        let packed = png.chunk_by_type("ruSt").unwrap().data();
+        assert!(packed.starts_with(b"PNGM"));
+        assert_eq!(envelope::unwrap(packed).unwrap(), b"packed");
+        assert_eq!(png.chunk_by_type("raWw").unwrap().data(), b"plain");
+    }
+
+    #[test]
+    fn test_signed_message() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let options = EncodeArgs { sign: Some("hunter2".to_string()), ..EncodeArgs::default() };
+        encode(&path, &chunk_type("ruSt"), "signed", &options, None, &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        let chunk = png.chunk_by_type("ruSt").unwrap();
+        let decode = DecodeArgs { sign: Some("hunter2".to_string()), ..DecodeArgs::default() };
+        assert_eq!(decode_messages(&[chunk], &decode).unwrap(), [("signed".to_string(), true)]);
+
+        let mut data = chunk.data().to_vec();
+        data[6] = b'S';
+        let tampered = Chunk::new(chunk_type("ruSt"), data);
+        let error = decode_messages(&[&tampered], &decode).unwrap_err();
+        assert!(error.to_string().starts_with("Signature mismatch"));
+        let wrong_key = DecodeArgs { sign: Some("hunter3".to_string()), ..DecodeArgs::default() };
+        assert!(decode_messages(&[chunk], &wrong_key).is_err());
+    }
+
+    #[test]
+    fn test_encode_replace_keeps_one_chunk() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let replace = EncodeArgs { replace: true, ..EncodeArgs::default() };
+
+        encode(&path, &chunk_type("ruSt"), "first", &replace, None, &WriteArgs::default()).unwrap();
+        encode(&path, &chunk_type("ruSt"), "second", &replace, None, &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        let message_type = chunk_type("ruSt");
+        let messages: Vec<_> = png.chunks_of_type(&message_type).collect();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].data(), b"second");
+        assert_eq!(png.chunks().last().unwrap().chunk_type(), &ChunkType::IEND);
+
+        encode(&path, &chunk_type("ruSt"), "third", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+        assert_eq!(Png::from_file(&path).unwrap().chunks_of_type(&message_type).count(), 2);
+    }
+
+    #[test]
+    fn test_check_encode_type() {
+        check_encode_type(&chunk_type("ruSt"), false, false).unwrap();
+        let error = check_encode_type(&chunk_type("RuSt"), false, false).unwrap_err();
+        assert!(error.to_string().contains("--allow-critical"));
+        check_encode_type(&chunk_type("RuSt"), true, false).unwrap();
+
+        let error = check_encode_type(&chunk_type("ruST"), false, false).unwrap_err();
+        assert!(error.to_string().contains("isn't safe to copy"));
+        for allow_critical in [false, true] {
+            let error = check_encode_type(&chunk_type("RuST"), allow_critical, false).unwrap_err();
+            assert!(error.to_string().contains("pass --"));
+        }
+        check_encode_type(&chunk_type("RuST"), false, true).unwrap();
+    }
+
+    #[test]
+    fn test_encode_nonstandard_type() {
+        assert!(parse_chunk_type(Some("Ru1t"), None, false).is_err());
+        let nonstandard = parse_chunk_type(Some("Ru1t"), None, true).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let output = dir.path().join("out.png");
+        encode(&path, &nonstandard, "secret", &EncodeArgs::default(), Some(&output), &WriteArgs::default()).unwrap();
+
+        let written = fs::read(&output).unwrap();
+        assert!(written.windows(10).any(|window| window == b"Ru1tsecret"));
+        // and pngme can open what it wrote
+        let png = Png::from_file(&output).unwrap();
+        assert_eq!(png.chunk_by_type("Ru1t").unwrap().data_as_string().unwrap(), "secret");
+        decode(&output, &nonstandard, &DecodeArgs::default(), OutputFormat::Text).unwrap();
+        validate_file(&output, true).unwrap();
+    }
+
+    #[test]
+    fn test_encode_with_type_hex() {
+        let chunk_type = parse_chunk_type(None, Some("52755374"), false).unwrap();
+        assert_eq!(&chunk_type.to_string(), "RuSt");
+        assert!(parse_chunk_type(Some("RuSt"), Some("52755374"), false).is_err());
+        assert!(parse_chunk_type(None, None, false).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        encode(&path, &chunk_type, "from hex", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunk_by_type("RuSt").unwrap().data_as_string().unwrap(), "from hex");
+
+        // any printable bytes work, and the file still opens
+        let nonstandard = parse_chunk_type(None, Some("52753174"), false).unwrap();
+        encode(&path, &nonstandard, "digits", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunk_by_type("Ru1t").unwrap().data_as_string().unwrap(), "digits");
+    }
+
+    #[test]
+    fn test_encode_refuses_existing_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let output = dir.path().join("existing.png");
+        fs::write(&output, b"keep me").unwrap();
+
+        let error = encode(&path, &chunk_type("ruSt"), "secret", &EncodeArgs::default(), Some(&output), &WriteArgs::default());
+        assert!(error.unwrap_err().to_string().contains("pass --force to overwrite"));
+        assert_eq!(fs::read(&output).unwrap(), b"keep me");
+
+        let write = WriteArgs { force: true, ..Default::default() };
+        encode(&path, &chunk_type("ruSt"), "secret", &EncodeArgs::default(), Some(&output), &write).unwrap();
+        let png = Png::from_file(&output).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(), "secret");
+    }
+
+    #[test]
+    fn test_encode_output_same_as_input_needs_no_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let same = dir.path().join(".").join("test.png");
+
+        encode(&path, &chunk_type("ruSt"), "secret", &EncodeArgs::default(), Some(&same), &WriteArgs::default()).unwrap();
+        assert!(Png::from_file(&path).unwrap().chunk_by_type("ruSt").is_some());
+    }
+
+    #[test]
+    fn test_failed_write_leaves_original_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let original = fs::read(&path).unwrap();
+
+        let result = write_atomically(&path, None, |writer| {
+            writer.write_all(b"half a png")?;
+            Err(io::Error::other("killed mid-write"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read(&path).unwrap(), original);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_in_place_encode_keeps_mode_and_mtime() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        let mtime = filetime::FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(&path, mtime).unwrap();
+
+        encode(&path, &chunk_type("ruSt"), "one", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_ne!(filetime::FileTime::from_last_modification_time(&metadata), mtime);
+
+        filetime::set_file_mtime(&path, mtime).unwrap();
+        let write = WriteArgs { preserve_times: true, ..Default::default() };
+        encode(&path, &chunk_type("ruSt"), "two", &EncodeArgs::default(), None, &write).unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o640);
+        assert_eq!(filetime::FileTime::from_last_modification_time(&metadata), mtime);
+    }
+
+    #[test]
+    fn test_dry_run_encode_leaves_file_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let original = fs::read(&path).unwrap();
+        let write = WriteArgs { dry_run: true, backup: Some(".bak".to_string()), ..Default::default() };
+
+        encode(&path, &chunk_type("ruSt"), "secret", &EncodeArgs::default(), None, &write).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), original);
+        assert!(!dir.path().join("test.png.bak").exists());
+    }
+
+    #[test]
+    fn test_extract_writes_raw_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        let payload = vec![0x00, 0xff, 0x10, 0x80, 0x7f];
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(Chunk::new(ChunkType::from_str("biNr").unwrap(), payload.clone()));
+        fs::write(&path, png.as_bytes()).unwrap();
+        let output_path = dir.path().join("payload.bin");
+
+        extract(&path, "biNr", &output_path, false, OutputFormat::Text).unwrap();
+
+        assert_eq!(fs::read(&output_path).unwrap(), payload);
+        assert!(extract(&path, "miSs", &output_path, false, OutputFormat::Text).is_err());
+    }
+
+    #[test]
+    fn test_export_edit_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let json_path = dir.path().join("chunks.json");
+        export(&path, &json_path, false, OutputFormat::Text).unwrap();
+
+        // drop IDAT and add a message before IEND, the way someone would by hand
+        let mut value: Value = serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+        let chunks = value["chunks"].as_array_mut().unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1], json!({ "type": "IDAT", "data": BASE64.encode("data") }));
+        chunks.remove(1);
+        chunks.insert(1, json!({ "type": "ruSt", "data": BASE64.encode("hidden") }));
+        fs::write(&json_path, value.to_string()).unwrap();
+
+        let output = dir.path().join("out.png");
+        import(&json_path, &output, &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&output).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(types, ["IHDR", "ruSt", "IEND"]);
+        assert_eq!(png.chunks()[1].data(), b"hidden");
+        assert_eq!(png.chunks()[1].crc(), chunk_from_strings("ruSt", "hidden").crc());
+        assert_eq!(chunks_to_json(png.chunks()), value);
+
+        // an existing output needs --force
+        assert!(export(&path, &json_path, false, OutputFormat::Text).is_err());
+    }
+
+    #[test]
+    fn test_export_import_nonstandard_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut png = Png::from_chunks(testing_chunks());
+        for chunk_type in [*b"Ru1t", *b"r\"\\'"] {
+            let chunk_type = ChunkType::from_bytes_relaxed(chunk_type).unwrap();
+            png.insert_before_iend(Chunk::new(chunk_type, b"odd".to_vec()));
+        }
+        let path = dir.path().join("test.png");
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        let json_path = dir.path().join("chunks.json");
+        export(&path, &json_path, false, OutputFormat::Text).unwrap();
+        let output = dir.path().join("out.png");
+        import(&json_path, &output, &WriteArgs::default()).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_import_rejects_bad_chunks() {
+        let bad = [
+            json!([]),
+            json!({ "chunks": [{ "type": "ru t", "data": "" }] }),
+            json!({ "chunks": [{ "type": "rüst", "data": "" }] }),
+            json!({ "chunks": [{ "type": "ru\u{1}t", "data": "" }] }),
+            json!({ "chunks": [{ "type": "ruSt", "data": "not base64!" }] }),
+            json!({ "chunks": [{ "data": "" }] }),
+        ];
+        for value in bad {
+            assert!(png_from_json(&value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_split_and_merge_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("tEXt", "Author\0me"));
+        png.insert_before_iend(chunk_from_strings("ruSt", "hidden"));
+        let path = dir.path().join("test.png");
+        fs::write(&path, png.as_bytes()).unwrap();
+        let image_path = dir.path().join("minimal.png");
+        let sidecar_path = dir.path().join("metadata.json");
+
+        // a dry run writes neither file
+        let dry_run = WriteArgs { dry_run: true, ..WriteArgs::default() };
+        split(&path, &image_path, &sidecar_path, &dry_run).unwrap();
+        assert!(!image_path.exists() && !sidecar_path.exists());
+
+        split(&path, &image_path, &sidecar_path, &WriteArgs::default()).unwrap();
+
+        let minimal = Png::from_file(&image_path).unwrap();
+        assert!(minimal.chunks().iter().all(|chunk| chunk.chunk_type().is_critical()));
+        assert_eq!(structure_problem(&minimal), None);
+        assert_eq!(minimal.chunks().len(), 3);
+
+        let value: Value = serde_json::from_str(&fs::read_to_string(&sidecar_path).unwrap()).unwrap();
+        let metadata = png_from_json(&value).unwrap();
+        assert_eq!(metadata.chunks(), &png.chunks()[2..4]);
+
+        merge(&sidecar_path, &image_path, None, &WriteArgs::default()).unwrap();
+        assert_eq!(fs::read(&image_path).unwrap(), png.as_bytes());
+
+        // both outputs exist now
+        assert!(split(&path, &image_path, &sidecar_path, &WriteArgs::default()).is_err());
+        let force = WriteArgs { force: true, ..WriteArgs::default() };
+        assert!(split(&path, &image_path, &image_path, &force).is_err());
+    }
+
+    #[test]
+    fn test_grep_finds_marker() {
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("ruSt", "nothing to see, password=hunter2"));
+        png.insert_before_iend(Chunk::new(chunk_type("biNa"), vec![0, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad]));
+
+        let matches = find_matches(&png, b"password");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].index, 2);
+        assert_eq!(*matches[0].chunk_type, chunk_type("ruSt"));
+        assert_eq!(matches[0].offset, 16);
+        let bytes = png.as_bytes();
+        let file_offset = matches[0].file_offset;
+        assert_eq!(&bytes[file_offset..file_offset + 8], b"password");
+
+        let pattern = parse_hex("de ad").unwrap();
+        let offsets: Vec<_> = find_matches(&png, &pattern).iter().map(|m| m.offset).collect();
+        assert_eq!(offsets, [1, 5]);
+        // IDAT's "data" is found like any other chunk's
+        assert_eq!(find_matches(&png, b"ata").len(), 1);
+        assert!(find_matches(&png, b"absent").is_empty());
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("DEADbeef").unwrap(), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(parse_hex("00 ff").unwrap(), [0x00, 0xff]);
+        assert!(parse_hex("abc").is_err());
+        assert!(parse_hex("zz").is_err());
+        assert!(parse_hex("+1").is_err());
+    }
+
+    #[test]
+    fn test_lsb_capacity() {
+        use flate2::write::ZlibEncoder;
+
+        // 4 x 4 RGBA, 8 bits per sample
+        let mut ihdr = Vec::new();
+        ihdr.extend(4u32.to_be_bytes());
+        ihdr.extend(4u32.to_be_bytes());
+        ihdr.extend([8, 6, 0, 0, 0]);
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        for _ in 0..4 {
+            encoder.write_all(&[0; 17]).unwrap();
+        }
+        let png = Png::from_chunks(vec![
+            Chunk::new(chunk_type("IHDR"), ihdr),
+            Chunk::new(chunk_type("IDAT"), encoder.finish().unwrap()),
+            chunk_from_strings("IEND", ""),
+        ]);
+
+        let header = ImageHeader::from_chunk(&png.chunks()[0]).unwrap();
+        assert_eq!(header.samples(), Some(64));
+        assert_eq!(inflate_idat(&png, &header).unwrap(), 68);
+        // 64 bits, less the 4 byte length header
+        assert_eq!(lsb_capacity_bytes(64), 4);
+        assert_eq!(lsb_capacity_bytes(16), 0);
+        let report = lsb_capacity_report(&png).unwrap();
+        assert_eq!(report["capacity"], 4);
+        assert_eq!(report["pixel_data"], 68);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        fs::write(&path, png.as_bytes()).unwrap();
+        lsb_capacity(&path, OutputFormat::Json).unwrap();
+        // testing_chunks has no real IHDR
+        assert!(lsb_capacity(&write_testing_png(dir.path()), OutputFormat::Text).is_err());
+    }
+
+    #[test]
+    fn test_inflate_idat_is_bounded() {
+        use flate2::write::ZlibEncoder;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[0; 100_000]).unwrap();
+        let idat = Chunk::new(chunk_type("IDAT"), encoder.finish().unwrap());
+        // 4 x 4 RGBA, the last byte says whether it's interlaced
+        for (interlace, limit) in [(0, 69), (1, 64 * 2 + 4 * 2 + 7 + 1)] {
+            let mut ihdr = Vec::new();
+            ihdr.extend(4u32.to_be_bytes());
+            ihdr.extend(4u32.to_be_bytes());
+            ihdr.extend([8, 6, 0, 0, interlace]);
+            let ihdr = Chunk::new(chunk_type("IHDR"), ihdr);
+            let header = ImageHeader::from_chunk(&ihdr).unwrap();
+            let png = Png::from_chunks(vec![ihdr, idat.clone(), chunk_from_strings("IEND", "")]);
+            assert_eq!(inflate_idat(&png, &header).unwrap(), limit);
+        }
+    }
+
+    #[test]
+    fn test_decode_verify_warns_on_bad_crc() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("ruSt", "secret"));
+        let mut bytes = png.as_bytes();
+        // flip a bit in the ruSt CRC, the last 4 bytes before IEND's 12
+        let crc_end = bytes.len() - 12;
+        bytes[crc_end - 1] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+
+        let message_type = chunk_type("ruSt");
+        let options = DecodeArgs::default();
+        assert!(decode(&path, &message_type, &options, OutputFormat::Text).is_err());
+        let options = DecodeArgs { verify: true, ..DecodeArgs::default() };
+        decode(&path, &message_type, &options, OutputFormat::Json).unwrap();
+
+        let lenient = Png::from_file_lenient(&path).unwrap();
+        let chunk = lenient.chunks_of_type(&message_type).next().unwrap();
+        assert_eq!(chunk.data(), b"secret");
+        let problem = crc_problem(chunk).unwrap();
+        assert!(problem.starts_with("ruSt chunk failed CRC verification"), "{problem}");
+        // the untouched chunks verify fine
+        let others = lenient.chunks().iter().filter(|c| c.chunk_type() != &message_type);
+        assert!(others.map(crc_problem).all(|problem| problem.is_none()));
+    }
+
+    #[test]
+    fn test_checksum_report_lists_one_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("ruSt", "secret"));
+        let mut bytes = png.as_bytes();
+        let crc_end = bytes.len() - 12;
+        bytes[crc_end - 1] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+
+        let lenient = Png::from_file_lenient(&path).unwrap();
+        let rows = checksum_rows(&lenient);
+        assert_eq!(rows.len(), 4);
+        let mismatches: Vec<String> = rows
+            .iter()
+            .filter(|(chunk, recomputed)| chunk.crc() != *recomputed)
+            .map(|(chunk, _)| chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(mismatches, ["ruSt"]);
+        assert_eq!(rows[3].1, rows[3].0.crc());
+
+        let error = checksum_report(&path, OutputFormat::Json).unwrap_err();
+        assert_eq!(error.to_string(), "1 of 4 chunks have a CRC that doesn't match");
+        checksum_report(&write_testing_png(dir.path()), OutputFormat::Text).unwrap();
+    }
+
+    #[test]
+    fn test_suffix_writes_a_sibling() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pic.png");
+        fs::write(&path, Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+        let original = fs::read(&path).unwrap();
+        let write = WriteArgs { suffix: Some("_stamped".to_string()), ..Default::default() };
+
+        let options = EncodeArgs::default();
+        encode(&path, &chunk_type("ruSt"), "stamp", &options, None, &write).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), original);
+        let stamped = Png::from_file(&dir.path().join("pic_stamped.png")).unwrap();
+        assert_eq!(stamped.chunk_by_type("ruSt").unwrap().data(), b"stamp");
+
+        // the sibling isn't replaced without --force, and --output can't be combined
+        assert!(encode(&path, &chunk_type("ruSt"), "again", &options, None, &write).is_err());
+        let output = dir.path().join("other.png");
+        assert!(encode(&path, &chunk_type("ruSt"), "x", &options, Some(&output), &write).is_err());
+    }
+
+    #[test]
+    fn test_strip_recursive_with_suffix() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("teXt", "metadata"));
+        for path in [dir.path().join("a.png"), nested.join("b.png")] {
+            fs::write(path, png.as_bytes()).unwrap();
+        }
+        let write = WriteArgs { suffix: Some("_out".to_string()), ..Default::default() };
+
+        strip_recursive(dir.path(), false, &write).unwrap();
+        assert_eq!(Png::from_file(&dir.path().join("a.png")).unwrap().chunks().len(), 4);
+        assert_eq!(Png::from_file(&nested.join("b_out.png")).unwrap().chunks().len(), 3);
+        // a second run skips the copies, so there's no b_out_out.png
+        strip_recursive(dir.path(), false, &WriteArgs { force: true, ..write }).unwrap();
+        assert!(!nested.join("b_out_out.png").exists());
+    }
+
+    #[test]
+    fn test_decode_all_with_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        let mut png = Png::from_chunks(testing_chunks());
+        for message in ["one", "two", "three"] {
+            png.insert_before_iend(chunk_from_strings("ruSt", message));
+        }
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        let options = DecodeArgs { all: true, count: Some(2), ..DecodeArgs::default() };
+        let png = Png::from_file(&path).unwrap();
+        let message_type = chunk_type("ruSt");
+        let matching: Vec<&Chunk> = png.chunks_of_type(&message_type).collect();
+        let messages = decode_messages(&matching[..2], &options).unwrap();
+        let mut out = Vec::new();
+        print_messages(&mut out, &chunk_type("ruSt"), &messages, 1, &options, OutputFormat::Text)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "one\ntwo\nnote: 1 more ruSt chunks not shown\n");
+
+        let mut out = Vec::new();
+        print_messages(&mut out, &chunk_type("ruSt"), &messages, 1, &options, OutputFormat::Json)
+            .unwrap();
+        let report: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(report["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(report["messages"][1]["message"], "two");
+        assert_eq!(report["not_shown"], 1);
+
+        // the whole path, count larger than the matches is fine
+        decode(&path, &chunk_type("ruSt"), &options, OutputFormat::Text).unwrap();
+        let options = DecodeArgs { all: true, count: Some(10), ..DecodeArgs::default() };
+        decode(&path, &chunk_type("ruSt"), &options, OutputFormat::Json).unwrap();
+    }
+
+    #[test]
+    fn test_encode_after() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let options = EncodeArgs { after: Some("IHDR".to_string()), ..EncodeArgs::default() };
+        encode(&path, &chunk_type("ruSt"), "early", &options, None, &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunks()[1].chunk_type(), &chunk_type("ruSt"));
+        assert_eq!(png.chunks()[1].data(), b"early");
+
+        let options = EncodeArgs { after: Some("tEXt".to_string()), ..EncodeArgs::default() };
+        let error = encode(&path, &chunk_type("ruSt"), "x", &options, None, &WriteArgs::default())
+            .unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(PngError::ChunkNotFound(_))));
+
+        let options = EncodeArgs { fallback: true, ..options };
+        encode(&path, &chunk_type("ruSt"), "late", &options, None, &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&path).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(types, ["IHDR", "ruSt", "IDAT", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_extract_icc() {
+        use flate2::write::ZlibEncoder;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&[0x42; 512]).unwrap();
+        let mut data = b"tiny profile\0\0".to_vec();
+        data.extend(encoder.finish().unwrap());
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(Chunk::new(chunk_type("iCCP"), data));
+        fs::write(&path, png.as_bytes()).unwrap();
+        let output_path = dir.path().join("profile.icc");
+
+        extract_icc(&path, &output_path, false, OutputFormat::Text).unwrap();
+        assert_eq!(fs::read(&output_path).unwrap(), [0x42; 512]);
+
+        let plain = dir.path().join("plain.png");
+        fs::write(&plain, Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+        let error = extract_icc(&plain, &output_path, true, OutputFormat::Text).unwrap_err();
+        assert!(error.to_string().contains("has no embedded ICC profile"));
+    }
+
+    #[test]
+    fn test_inject_then_extract_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let payload: Vec<u8> = (0..=255).collect();
+        let payload_path = dir.path().join("payload.bin");
+        fs::write(&payload_path, &payload).unwrap();
+
+        inject(&path, "biNr", Some(&payload_path), None, &WriteArgs::default()).unwrap();
+        let output_path = dir.path().join("extracted.bin");
+        extract(&path, "biNr", &output_path, false, OutputFormat::Text).unwrap();
+
+        assert_eq!(fs::read(&output_path).unwrap(), payload);
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunks().last().unwrap().chunk_type(), &ChunkType::IEND);
+    }
+
+    #[test]
+    fn test_inject_hex_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        inject(&path, "ruSt", None, Some("48656c6c6f"), &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        let chunk = png.chunk_by_type("ruSt").unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "Hello");
+
+        for bad in ["48656c6c6", "48zz"] {
+            let error = inject(&path, "ruSt", None, Some(bad), &WriteArgs::default()).unwrap_err();
+            assert!(error.to_string().contains("isn't a sequence of hex bytes"));
+        }
+    }
+
+    #[test]
+    fn test_replace_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let mut png = Png::from_file(&path).unwrap();
+        png.insert_after_type(&ChunkType::IHDR, chunk_from_strings("tEXt", "Author\0Old Name")).unwrap();
+        png.insert_before_iend(chunk_from_strings("tEXt", "Title\0Sunset"));
+        png.insert_before_iend(chunk_from_strings("tEXt", "Author\0Second"));
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        replace_text(&path, "Author", "New Name", &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&path).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(types, ["IHDR", "tEXt", "IDAT", "tEXt", "IEND"]);
+        assert_eq!(png.chunks()[1].data(), b"Author\0New Name");
+        assert_eq!(png.chunks()[3].data(), b"Title\0Sunset");
+
+        replace_text(&path, "Comment", "fresh", &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunks().len(), 6);
+        assert_eq!(png.chunks()[4].data(), b"Comment\0fresh");
+        assert_eq!(png.chunks()[5].chunk_type(), &ChunkType::IEND);
+
+        assert!(replace_text(&path, "", "no keyword", &WriteArgs::default()).is_err());
+    }
+
+    #[test]
+    fn test_reorder() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let mut png = Png::from_file(&path).unwrap();
+        png.insert_before_iend(Chunk::new(chunk_type("gAMA"), 45455u32.to_be_bytes().to_vec()));
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        reorder(&path, "gAMA", 1, false, &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&path).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(types, ["IHDR", "gAMA", "IDAT", "IEND"]);
+
+        let before = fs::read(&path).unwrap();
+        let error = reorder(&path, "gAMA", 0, false, &WriteArgs::default()).unwrap_err();
+        assert!(error.to_string().contains("take IHDR off index 0"));
+        let error = reorder(&path, "IEND", 2, false, &WriteArgs::default()).unwrap_err();
+        assert!(error.to_string().contains("leave IEND before the end"));
+        assert!(reorder(&path, "gAMA", 4, false, &WriteArgs::default()).is_err());
+        assert_eq!(fs::read(&path).unwrap(), before);
+
+        // --force is about backups and outputs, it doesn't let the ends move
+        let force = WriteArgs { force: true, ..WriteArgs::default() };
+        assert!(reorder(&path, "IHDR", 1, false, &force).is_err());
+        reorder(&path, "IHDR", 1, true, &WriteArgs::default()).unwrap();
+        assert_eq!(Png::from_file(&path).unwrap().chunks()[1].chunk_type(), &ChunkType::IHDR);
+    }
+
+    #[test]
+    fn test_encode_append_file() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let source_path = write_testing_png(source_dir.path());
+        let mut source = Png::from_file(&source_path).unwrap();
+        source.insert_before_iend(chunk_from_strings("tEXt", "Author\0Someone"));
+        source.insert_before_iend(chunk_from_strings("gAMA", "gama"));
+        source.insert_before_iend(chunk_from_strings("PLTE", "rgb"));
+        fs::write(&source_path, source.as_bytes()).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let mut png = Png::from_file(&path).unwrap();
+        png.insert_before_iend(chunk_from_strings("gAMA", "gama"));
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        encode_append_only(&path, &source_path, None, &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&path).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "gAMA", "tEXt", "IEND"]);
+
+        // alongside a message, and nothing doubles up the second time round
+        let options = EncodeArgs { append_file: Some(source_path.clone()), ..EncodeArgs::default() };
+        encode(&path, &chunk_type("ruSt"), "both", &options, None, &WriteArgs::default()).unwrap();
+        let png = Png::from_file(&path).unwrap();
+        let types: Vec<String> = png.chunks().iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(types, ["IHDR", "IDAT", "gAMA", "tEXt", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_merge_message_chunk() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let source_path = write_testing_png(source_dir.path());
+        encode(&source_path, &chunk_type("ruSt"), "stamped", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+        let destination_dir = tempfile::tempdir().unwrap();
+        let destination_path = write_testing_png(destination_dir.path());
+
+        merge(&source_path, &destination_path, Some("ruSt"), &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&destination_path).unwrap();
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(), "stamped");
+        assert_eq!(png.chunks().last().unwrap().chunk_type(), &ChunkType::IEND);
+    }
+
+    #[test]
+    fn test_merge_never_duplicates_ihdr_or_iend() {
+        let mut source = Png::from_chunks(testing_chunks());
+        source.insert_before_iend(chunk_from_strings("tEXt", "Author\0me"));
+        source.insert_before_iend(chunk_from_strings("IDAT", "critical"));
+        let mut destination = Png::from_chunks(testing_chunks());
+
+        assert_eq!(merge_chunks(&source, &mut destination, None), 1);
+        assert_eq!(merge_chunks(&source, &mut destination, Some(&ChunkType::IHDR)), 0);
+        assert_eq!(merge_chunks(&source, &mut destination, Some(&ChunkType::IEND)), 0);
+
+        let types: Vec<String> = destination.chunks().iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "tEXt", "IEND"]);
+    }
+
+    #[test]
+    fn test_strip_trailing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let original = fs::read(&path).unwrap();
+        let mut with_junk = original.clone();
+        with_junk.extend_from_slice(b"crudely appended");
+        fs::write(&path, &with_junk).unwrap();
+
+        strip_trailing(&path, &WriteArgs::default()).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), original);
+        assert!(Png::from_file(&path).unwrap().trailing_bytes().is_empty());
+        strip_trailing(&path, &WriteArgs::default()).unwrap();
+    }
+
+    #[test]
+    fn test_fix_end_adds_one_iend() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        let mut chunks = testing_chunks();
+        chunks.pop();
+        let truncated = Png::from_chunks(chunks);
+        fs::write(&path, truncated.as_bytes()).unwrap();
+        assert!(!ordering_violations(&Png::from_file(&path).unwrap()).is_empty());
+
+        fix_end(&path, &WriteArgs::default()).unwrap();
+        fix_end(&path, &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunks_of_type(&ChunkType::IEND).count(), 1);
+        assert!(ordering_violations(&png).is_empty());
+        assert_eq!(png.as_bytes(), Png::from_chunks(testing_chunks()).as_bytes());
+    }
+
+    #[test]
+    fn test_matching_ignores_case_only_when_asked() {
+        let mut png = Png::from_chunks(testing_chunks());
+        png.insert_before_iend(chunk_from_strings("RuSt", "shouty"));
+        png.insert_before_iend(chunk_from_strings("ruSt", "quiet"));
+        let query = chunk_type("rust");
+
+        assert!(matching_chunks(&png, &query, false).is_empty());
+        let found: Vec<String> =
+            matching_chunks(&png, &query, true).iter().map(|c| c.chunk_type_str()).collect();
+        assert_eq!(found, ["RuSt", "ruSt"]);
+        assert_eq!(matching_chunks(&png, &chunk_type("ruSt"), false).len(), 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        fs::write(&path, png.as_bytes()).unwrap();
+        let options = DecodeArgs { ignore_case: true, ..DecodeArgs::default() };
+        decode(&path, &query, &options, OutputFormat::Text).unwrap();
+        assert!(decode(&path, &query, &DecodeArgs::default(), OutputFormat::Text).is_err());
+    }
+
+    #[test]
+    fn test_fragmented_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        let message = "split into three fragments of up to twenty bytes";
+        let options = EncodeArgs { fragment: Some(28), ..EncodeArgs::default() };
+        encode(&path, &chunk_type("ruSt"), message, &options, None, &WriteArgs::default()).unwrap();
+
+        let mut png = Png::from_file(&path).unwrap();
+        let message_type = chunk_type("ruSt");
+        let chunks: Vec<&Chunk> = png.chunks_of_type(&message_type).collect();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|chunk| chunk.length() <= 28));
+        let (data, crc_ok) = join_fragments(&chunks, true, None).unwrap();
+        assert_eq!(data, message.as_bytes());
+        assert!(crc_ok);
+        decode(&path, &message_type, &DecodeArgs::default(), OutputFormat::Text).unwrap();
+
+        // losing the middle fragment is reported, not glossed over
+        let middle = png.chunks().iter().position(|chunk| chunk.chunk_type() == &message_type).unwrap() + 1;
+        let mut chunks = png.chunks().to_vec();
+        chunks.remove(middle);
+        png = Png::from_chunks(chunks);
+        let chunks: Vec<&Chunk> = png.chunks_of_type(&message_type).collect();
+        let error = join_fragments(&chunks, false, None).unwrap_err();
+        assert_eq!(error.to_string(), "Found fragment 2 of 3 where fragment 1 should be");
+    }
+
+    #[test]
+    fn test_dedup_collapses_identical_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        encode(&path, &chunk_type("ruSt"), "twice", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+        encode(&path, &chunk_type("ruSt"), "twice", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+
+        dedup(&path, false, &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunks_of_type(&chunk_type("ruSt")).count(), 1);
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_hash_normalize_ignores_trailing_bytes() {
+        let clean = Png::from_chunks(testing_chunks()).as_bytes();
+        let mut junk = clean.clone();
+        junk.extend_from_slice(b"appended garbage");
+        let clean = Png::try_from(clean.as_slice()).unwrap();
+        let junk = Png::try_from(junk.as_slice()).unwrap();
+
+        assert_ne!(png_hash(&clean, false), png_hash(&junk, false));
+        assert_eq!(png_hash(&clean, true), png_hash(&junk, true));
+        assert_eq!(png_hash(&clean, true), png_hash(&clean, false));
+        assert_eq!(png_hash(&clean, false).len(), 64);
+    }
+
+    #[test]
+    fn test_touch_sets_current_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+
+        touch(&path, &WriteArgs::default()).unwrap();
+        touch(&path, &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunks_of_type(&chunk_type("tIME")).count(), 1);
+        let stamped = LastModified::from_chunk(png.chunk_by_type("tIME").unwrap()).unwrap();
+        let elapsed = chrono::Utc::now() - stamped.to_datetime().unwrap();
+        assert!(elapsed.num_seconds().abs() <= 5, "tIME is {elapsed} away from now");
+        assert_eq!(png.chunks().last().unwrap().chunk_type(), &ChunkType::IEND);
+    }
+
+    #[test]
+    fn test_rename_type_then_decode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        encode(&path, &chunk_type("ruSt"), "moved", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+
+        rename_type(&path, "ruSt", "teSt", &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        assert_eq!(png.chunk_by_type("teSt").unwrap().data_as_string().unwrap(), "moved");
+        assert!(png.chunk_by_type("ruSt").is_none());
+        assert!(rename_type(&path, "teSt", "te5t", &WriteArgs::default()).is_err());
+    }
+
+    #[test]
+    fn test_set_bits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_testing_png(dir.path());
+        encode(&path, &chunk_type("ruSt"), "bits", &EncodeArgs::default(), None, &WriteArgs::default()).unwrap();
+
+        set_bits(&path, "ruSt", None, Some(false), &WriteArgs::default()).unwrap();
+
+        let png = Png::from_file(&path).unwrap();
+        let chunk = png.chunk_by_type("ruST").unwrap();
+        assert!(!chunk.chunk_type().is_safe_to_copy());
+        assert_eq!(chunk.data_as_string().unwrap(), "bits");
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let first = Png::from_chunks(testing_chunks());
+        let second = Png::from_chunks(testing_chunks());
+        assert!(diff_chunks(&first, &second).is_empty());
+    }
+
+    #[test]
+    fn test_diff_extra_chunk() {
+        let first = Png::from_chunks(testing_chunks());
+        let mut second = Png::from_chunks(testing_chunks());
+        second.append_chunk(chunk_from_strings("ruSt", "secret"));
+
+        let diffs = diff_chunks(&first, &second);
+        assert_eq!(diffs, vec![ChunkDiff::Added(3, &second.chunks()[3])]);
+        let expected = format!("+ chunk 3: ruSt (length 6, crc {})", second.chunks()[3].crc());
+        assert_eq!(diffs[0].describe(), expected);
+
+        let diffs = diff_chunks(&second, &first);
+        assert_eq!(diffs, vec![ChunkDiff::Removed(3, &second.chunks()[3])]);
+    }
+
+    #[test]
+    fn test_diff_changed_chunk() {
+        let first = Png::from_chunks(testing_chunks());
+        let second = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header"),
+            chunk_from_strings("IDAT", "other data"),
+            chunk_from_strings("IEND", ""),
+        ]);
+
+        let diffs = diff_chunks(&first, &second);
+        assert_eq!(diffs, vec![ChunkDiff::Changed(1, &first.chunks()[1], &second.chunks()[1])]);
+    }
+
+    #[test]
+    fn test_capacity_report() {
+        let mut png = Png::from_chunks(testing_chunks());
+        png.append_chunk(chunk_from_strings("ruSt", "first message"));
+        png.append_chunk(chunk_from_strings("ruSt", "second"));
+        png.append_chunk(chunk_from_strings("teSt", "ignored"));
+
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let report = CapacityReport::new(&png, &chunk_type);
+        assert_eq!(report, CapacityReport { chunks: 2, used: 19, largest: 13 });
+
+        let chunk_type = ChunkType::from_str("abCd").unwrap();
+        let report = CapacityReport::new(&png, &chunk_type);
+        assert_eq!(report, CapacityReport { chunks: 0, used: 0, largest: 0 });
+    }
+
+    #[test]
+    fn test_validation_warns_on_trailing_data() {
+        let mut bytes = Png::from_chunks(testing_chunks()).as_bytes();
+        let end = bytes.len();
+        bytes.extend_from_slice(b"junk");
+        let png = Png::try_from(&bytes).unwrap();
+
+        let expected = vec![format!("4 bytes of trailing data after IEND at offset {end}")];
+        assert_eq!(validation_warnings(&png), expected);
+    }
+
+    #[test]
+    fn test_validation_warns_on_duplicate_ihdr() {
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header"),
+            chunk_from_strings("IHDR", "header again"),
+            chunk_from_strings("IDAT", "first"),
+            chunk_from_strings("IDAT", "second"),
+            chunk_from_strings("IEND", ""),
+        ]);
+
+        let expected = vec!["IHDR appears 2 times but may only appear once"];
+        assert_eq!(validation_warnings(&png), expected);
+    }
+}
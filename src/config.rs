@@ -0,0 +1,135 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::args::{self, OutputFormat};
+
+pub const FILE_NAME: &str = "pngme.toml";
+
+// defaults for options that would otherwise be repeated on every run, a flag given on the
+// command line always wins
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // used by encode, decode and remove when no chunk type is given
+    pub chunk_type: Option<String>,
+    pub backup: Option<Backup>,
+    pub format: Option<OutputFormat>,
+    // same syntax as --max-size, e.g. "64M"
+    pub max_size: Option<String>,
+}
+
+// `backup = true` uses the same .bak suffix as a bare --backup
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Backup {
+    Enabled(bool),
+    Suffix(String),
+}
+
+impl Config {
+    // the first of ./pngme.toml and $XDG_CONFIG_HOME/pngme/pngme.toml that exists, no file
+    // at all just means no defaults
+    pub fn load() -> Result<Config> {
+        for path in search_paths() {
+            if let Some(config) = Config::from_file(&path)? {
+                return Ok(config);
+            }
+        }
+        Ok(Config::default())
+    }
+
+    pub fn from_file(path: &Path) -> Result<Option<Config>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(error).with_context(|| format!("Failed to read {}", path.display()))
+            }
+        };
+        let config = Config::parse(&text).with_context(|| format!("Invalid config file {}", path.display()))?;
+        Ok(Some(config))
+    }
+
+    // the size is checked here so a typo is reported against the config file
+    pub fn parse(text: &str) -> Result<Config> {
+        let config: Config = toml::from_str(text)?;
+        config.max_size()?;
+        Ok(config)
+    }
+
+    pub fn backup_suffix(&self) -> Option<String> {
+        match &self.backup {
+            Some(Backup::Enabled(true)) => Some(".bak".to_string()),
+            Some(Backup::Enabled(false)) | None => None,
+            Some(Backup::Suffix(suffix)) => Some(suffix.clone()),
+        }
+    }
+
+    pub fn max_size(&self) -> Result<Option<u64>> {
+        self.max_size
+            .as_deref()
+            .map(|size| args::parse_size(size).map_err(anyhow::Error::msg).context("Invalid max_size"))
+            .transpose()
+    }
+}
+
+fn search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(FILE_NAME)];
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    if let Some(config_home) = config_home {
+        paths.push(config_home.join("pngme").join(FILE_NAME));
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+            chunk_type = "ruSt"
+            backup = ".orig"
+            format = "json"
+            max_size = "64M"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.chunk_type.as_deref(), Some("ruSt"));
+        assert_eq!(config.backup_suffix().as_deref(), Some(".orig"));
+        assert_eq!(config.format, Some(OutputFormat::Json));
+        assert_eq!(config.max_size().unwrap(), Some(64 * 1024 * 1024));
+
+        assert_eq!(Config::parse("backup = true").unwrap().backup_suffix().as_deref(), Some(".bak"));
+        assert_eq!(Config::parse("backup = false").unwrap().backup_suffix(), None);
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_bad_config() {
+        assert!(Config::parse("chunk_typ = \"ruSt\"").is_err());
+        assert!(Config::parse("format = \"yaml\"").is_err());
+        assert!(Config::parse("max_size = \"lots\"").is_err());
+        assert!(Config::parse("backup = 3").is_err());
+    }
+
+    #[test]
+    fn test_missing_file_is_not_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(Config::from_file(&dir.path().join(FILE_NAME)).unwrap(), None);
+
+        fs::write(dir.path().join(FILE_NAME), "chunk_type = \"ruSt\"").unwrap();
+        let config = Config::from_file(&dir.path().join(FILE_NAME)).unwrap().unwrap();
+        assert_eq!(config.chunk_type.as_deref(), Some("ruSt"));
+    }
+}
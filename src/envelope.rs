@@ -0,0 +1,415 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+
+// a wrapped message is MAGIC, a version byte, a flags byte and then the payload
+pub const MAGIC: [u8; 4] = *b"PNGM";
+pub const VERSION: u8 = 1;
+const HEADER_LENGTH: usize = 6;
+
+// a fragment is FRAGMENT_MAGIC, its 0 based index and the fragment count, both big endian
+// u16, and then its slice of the data
+pub const FRAGMENT_MAGIC: [u8; 4] = *b"PNGF";
+const FRAGMENT_HEADER_LENGTH: usize = 8;
+
+const COMPRESSED_BIT: u8 = 0b001;
+const ENCRYPTED_BIT: u8 = 0b010;
+const BASE64_BIT: u8 = 0b100;
+// a signed envelope ends in an HMAC-SHA256 of everything before it, header included
+const SIGNED_BIT: u8 = 0b1000;
+const SIGNATURE_LENGTH: usize = 32;
+
+// DEFLATE levels as zlib counts them
+pub const DEFAULT_LEVEL: u32 = 6;
+pub const MAX_LEVEL: u32 = 9;
+
+#[derive(Debug, Error)]
+enum EnvelopeError {
+    #[error("Message envelope is truncated, expected at least {HEADER_LENGTH} bytes")]
+    Truncated,
+    #[error("Unsupported message envelope version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Unknown message envelope flags {0:#010b}")]
+    UnknownFlags(u8),
+    #[error("Encrypted messages aren't supported yet")]
+    Encrypted,
+    #[error("Compression level {0} is out of range, expected 0 to {MAX_LEVEL}")]
+    CompressionLevel(u32),
+    #[error("Signature mismatch, the message was altered or the key is wrong")]
+    SignatureMismatch,
+    #[error("Message isn't signed, so there's nothing to verify the key against")]
+    NotSigned,
+    #[error("Signed message is too short to hold its {SIGNATURE_LENGTH} byte signature")]
+    SignatureTruncated,
+    #[error("Fragment size {0} leaves no room for data after the {FRAGMENT_HEADER_LENGTH} byte header")]
+    FragmentTooSmall(usize),
+    #[error("Message needs {0} fragments, at most {max} are supported", max = u16::MAX)]
+    TooManyFragments(usize),
+    #[error("Fragment header is truncated, expected at least {FRAGMENT_HEADER_LENGTH} bytes")]
+    FragmentTruncated,
+    #[error("Found fragment {found} of {count} where fragment {expected} should be")]
+    FragmentOutOfOrder { found: u16, expected: u16, count: u16 },
+    #[error("Fragment {index} says there are {found} fragments, the first said {count}")]
+    FragmentCountMismatch { index: u16, found: u16, count: u16 },
+    #[error("Only {found} of {count} fragments found, fragment {found} onwards is missing")]
+    FragmentsMissing { found: u16, count: u16 },
+}
+
+// which transforms were applied to the payload, one bit each in the flags byte
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Transforms {
+    pub compressed: bool,
+    pub encrypted: bool,
+    pub base64: bool,
+}
+
+impl Transforms {
+    // nothing to record, so the message can be stored as is
+    pub fn is_empty(&self) -> bool {
+        *self == Transforms::default()
+    }
+
+    fn flags(&self) -> u8 {
+        let mut flags = 0;
+        if self.compressed {
+            flags |= COMPRESSED_BIT;
+        }
+        if self.encrypted {
+            flags |= ENCRYPTED_BIT;
+        }
+        if self.base64 {
+            flags |= BASE64_BIT;
+        }
+        flags
+    }
+
+    fn from_flags(flags: u8) -> Result<Transforms> {
+        if flags & !(COMPRESSED_BIT | ENCRYPTED_BIT | BASE64_BIT) != 0 {
+            bail!(EnvelopeError::UnknownFlags(flags));
+        }
+        Ok(Transforms {
+            compressed: flags & COMPRESSED_BIT != 0,
+            encrypted: flags & ENCRYPTED_BIT != 0,
+            base64: flags & BASE64_BIT != 0,
+        })
+    }
+}
+
+// compresses first and base64 encodes last, so a base64 payload is always printable
+pub fn wrap(message: &[u8], transforms: Transforms) -> Result<Vec<u8>> {
+    wrap_with(message, transforms, None, DEFAULT_LEVEL)
+}
+
+// with a key the envelope gets the signed flag and an HMAC of itself on the end. `level`
+// only matters when compressing, 0 stores and 9 is smallest but slowest
+pub fn wrap_with(
+    message: &[u8],
+    transforms: Transforms,
+    key: Option<&[u8]>,
+    level: u32,
+) -> Result<Vec<u8>> {
+    if transforms.encrypted {
+        bail!(EnvelopeError::Encrypted);
+    }
+    if level > MAX_LEVEL {
+        bail!(EnvelopeError::CompressionLevel(level));
+    }
+
+    let mut payload = message.to_vec();
+    if transforms.compressed {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+        encoder.write_all(&payload)?;
+        payload = encoder.finish().context("Failed to compress message")?;
+    }
+    if transforms.base64 {
+        payload = BASE64.encode(payload).into_bytes();
+    }
+
+    let mut data = Vec::with_capacity(HEADER_LENGTH + payload.len());
+    data.extend_from_slice(&MAGIC);
+    data.push(VERSION);
+    match key {
+        Some(key) => {
+            data.push(transforms.flags() | SIGNED_BIT);
+            data.extend_from_slice(&payload);
+            let signature = signer(key).chain_update(&data).finalize().into_bytes();
+            data.extend_from_slice(&signature);
+        }
+        None => {
+            data.push(transforms.flags());
+            data.extend_from_slice(&payload);
+        }
+    }
+    Ok(data)
+}
+
+fn signer(key: &[u8]) -> Hmac<Sha256> {
+    Hmac::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+// data without the magic wasn't wrapped and is returned unchanged
+pub fn unwrap(data: &[u8]) -> Result<Vec<u8>> {
+    unwrap_signed(data, None)
+}
+
+// with a key the envelope has to be signed with that same key. Without one a signature is
+// dropped unchecked, so decoding works the same as before for anyone not asking
+pub fn unwrap_signed(data: &[u8], key: Option<&[u8]>) -> Result<Vec<u8>> {
+    if !data.starts_with(&MAGIC) {
+        if key.is_some() {
+            bail!(EnvelopeError::NotSigned);
+        }
+        return Ok(data.to_vec());
+    }
+    let header = data.get(..HEADER_LENGTH).ok_or(EnvelopeError::Truncated)?;
+    if header[4] != VERSION {
+        bail!(EnvelopeError::UnsupportedVersion(header[4]));
+    }
+    let signed = header[5] & SIGNED_BIT != 0;
+    let transforms = Transforms::from_flags(header[5] & !SIGNED_BIT)?;
+    if transforms.encrypted {
+        bail!(EnvelopeError::Encrypted);
+    }
+
+    let mut end = data.len();
+    if signed {
+        end = end
+            .checked_sub(SIGNATURE_LENGTH)
+            .filter(|&end| end >= HEADER_LENGTH)
+            .ok_or(EnvelopeError::SignatureTruncated)?;
+    }
+    match key {
+        Some(_) if !signed => bail!(EnvelopeError::NotSigned),
+        Some(key) => signer(key)
+            .chain_update(&data[..end])
+            .verify_slice(&data[end..])
+            .map_err(|_| EnvelopeError::SignatureMismatch)?,
+        None => {}
+    }
+
+    let mut payload = data[HEADER_LENGTH..end].to_vec();
+    if transforms.base64 {
+        payload = BASE64.decode(&payload).context("Message payload isn't valid base64")?;
+    }
+    if transforms.compressed {
+        let mut message = Vec::new();
+        ZlibDecoder::new(payload.as_slice())
+            .read_to_end(&mut message)
+            .context("Failed to decompress message")?;
+        payload = message;
+    }
+    Ok(payload)
+}
+
+// splits already wrapped data into pieces of at most `size` bytes, headers included.
+// Empty data still becomes one fragment
+pub fn fragment(data: &[u8], size: usize) -> Result<Vec<Vec<u8>>> {
+    let Some(room) = size.checked_sub(FRAGMENT_HEADER_LENGTH).filter(|&room| room > 0) else {
+        bail!(EnvelopeError::FragmentTooSmall(size));
+    };
+    let pieces: Vec<&[u8]> = if data.is_empty() { vec![data] } else { data.chunks(room).collect() };
+    let count = u16::try_from(pieces.len())
+        .map_err(|_| EnvelopeError::TooManyFragments(pieces.len()))?;
+    let fragments = pieces
+        .into_iter()
+        .zip(0..)
+        .map(|(piece, index): (&[u8], u16)| {
+            let mut fragment = Vec::with_capacity(FRAGMENT_HEADER_LENGTH + piece.len());
+            fragment.extend_from_slice(&FRAGMENT_MAGIC);
+            fragment.extend_from_slice(&index.to_be_bytes());
+            fragment.extend_from_slice(&count.to_be_bytes());
+            fragment.extend_from_slice(piece);
+            fragment
+        })
+        .collect();
+    Ok(fragments)
+}
+
+pub fn is_fragment(data: &[u8]) -> bool {
+    data.starts_with(&FRAGMENT_MAGIC)
+}
+
+// joins fragments given in file order back into the wrapped data, anything missing,
+// repeated or out of order is an error rather than a silently garbled message
+pub fn reassemble(fragments: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut count = None;
+    for (expected, fragment) in (0..).zip(fragments) {
+        let header = fragment.get(..FRAGMENT_HEADER_LENGTH).ok_or(EnvelopeError::FragmentTruncated)?;
+        let index = u16::from_be_bytes([header[4], header[5]]);
+        let found_count = u16::from_be_bytes([header[6], header[7]]);
+        let count = *count.get_or_insert(found_count);
+        if found_count != count {
+            bail!(EnvelopeError::FragmentCountMismatch { index, found: found_count, count });
+        }
+        if index != expected {
+            bail!(EnvelopeError::FragmentOutOfOrder { found: index, expected, count });
+        }
+        data.extend_from_slice(&fragment[FRAGMENT_HEADER_LENGTH..]);
+    }
+    let found = fragments.len() as u16;
+    match count {
+        Some(count) if found < count => bail!(EnvelopeError::FragmentsMissing { found, count }),
+        _ => Ok(data),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_transforms() -> Vec<Transforms> {
+        let mut all = Vec::new();
+        for flags in 0..8 {
+            all.push(Transforms::from_flags(flags).unwrap());
+        }
+        all
+    }
+
+    #[test]
+    fn test_round_trip_each_combination() {
+        let message = b"This is where your secret message will be!".repeat(4);
+        for transforms in all_transforms().into_iter().filter(|t| !t.encrypted) {
+            let wrapped = wrap(&message, transforms).unwrap();
+            assert_eq!(&wrapped[..4], b"PNGM");
+            assert_eq!(wrapped[4], VERSION);
+            assert_eq!(wrapped[5], transforms.flags());
+            assert_eq!(unwrap(&wrapped).unwrap(), message, "{transforms:?}");
+        }
+    }
+
+    #[test]
+    fn test_encrypted_is_rejected() {
+        for transforms in all_transforms().into_iter().filter(|t| t.encrypted) {
+            assert!(wrap(b"secret", transforms).is_err());
+
+            let mut data = MAGIC.to_vec();
+            data.extend([VERSION, transforms.flags()]);
+            data.extend_from_slice(b"secret");
+            let error = unwrap(&data).unwrap_err();
+            assert!(matches!(error.downcast_ref(), Some(EnvelopeError::Encrypted)));
+        }
+    }
+
+    #[test]
+    fn test_compressed_payload_is_smaller() {
+        let message = b"aaaa".repeat(256);
+        let transforms = Transforms { compressed: true, ..Transforms::default() };
+        assert!(wrap(&message, transforms).unwrap().len() < message.len());
+    }
+
+    #[test]
+    fn test_compression_levels() {
+        let message = b"aaaa".repeat(256);
+        let transforms = Transforms { compressed: true, ..Transforms::default() };
+        let stored = wrap_with(&message, transforms, None, 0).unwrap();
+        let smallest = wrap_with(&message, transforms, None, MAX_LEVEL).unwrap();
+        assert!(stored.len() > message.len());
+        assert!(smallest.len() < stored.len());
+        for wrapped in [stored, smallest] {
+            assert_eq!(unwrap(&wrapped).unwrap(), message);
+        }
+
+        let error = wrap_with(&message, transforms, None, 10).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EnvelopeError::CompressionLevel(10))));
+    }
+
+    #[test]
+    fn test_raw_data_passes_through() {
+        assert_eq!(unwrap(b"plain message").unwrap(), b"plain message");
+        assert_eq!(unwrap(b"").unwrap(), b"");
+    }
+
+    #[test]
+    fn test_fragment_round_trip() {
+        let data = b"twenty one bytes long";
+        let fragments = fragment(data, 15).unwrap();
+        assert_eq!(fragments.len(), 3);
+        assert!(fragments.iter().all(|fragment| fragment.len() <= 15 && is_fragment(fragment)));
+        assert_eq!(&fragments[2][..8], b"PNGF\x00\x02\x00\x03");
+
+        let slices: Vec<&[u8]> = fragments.iter().map(Vec::as_slice).collect();
+        assert_eq!(reassemble(&slices).unwrap(), data);
+        assert_eq!(fragment(b"", 15).unwrap(), [b"PNGF\x00\x00\x00\x01"]);
+        assert!(matches!(
+            fragment(data, 8).unwrap_err().downcast_ref(),
+            Some(EnvelopeError::FragmentTooSmall(8))
+        ));
+    }
+
+    #[test]
+    fn test_reassemble_errors() {
+        let fragments = fragment(b"twenty one bytes long", 15).unwrap();
+        let [first, second, third] = [&fragments[0][..], &fragments[1][..], &fragments[2][..]];
+
+        let error = reassemble(&[first, third, second]).unwrap_err();
+        assert_eq!(error.to_string(), "Found fragment 2 of 3 where fragment 1 should be");
+        let error = reassemble(&[first, second]).unwrap_err();
+        assert_eq!(error.to_string(), "Only 2 of 3 fragments found, fragment 2 onwards is missing");
+        let error = reassemble(&[first, first]).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EnvelopeError::FragmentOutOfOrder { .. })));
+        let other = fragment(b"other", 15).unwrap();
+        let error = reassemble(&[first, &other[0]]).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EnvelopeError::FragmentCountMismatch { .. })));
+        assert!(reassemble(&[b"PNGF\x00"]).is_err());
+    }
+
+    #[test]
+    fn test_bad_headers() {
+        let error = unwrap(b"PNGM\x01").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EnvelopeError::Truncated)));
+        let error = unwrap(b"PNGM\x09\x00hi").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EnvelopeError::UnsupportedVersion(9))));
+        let error = unwrap(b"PNGM\x01\x10hi").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EnvelopeError::UnknownFlags(16))));
+    }
+
+    #[test]
+    fn test_signed_round_trip() {
+        let transforms = Transforms { compressed: true, ..Transforms::default() };
+        let signed = wrap_with(b"tamper evident", transforms, Some(b"key"), DEFAULT_LEVEL).unwrap();
+        assert_eq!(signed[5], COMPRESSED_BIT | SIGNED_BIT);
+        assert_eq!(unwrap_signed(&signed, Some(b"key")).unwrap(), b"tamper evident");
+        // without a key the signature is just dropped
+        assert_eq!(unwrap(&signed).unwrap(), b"tamper evident");
+
+        let signed = wrap_with(b"", Transforms::default(), Some(b"key"), DEFAULT_LEVEL).unwrap();
+        assert_eq!(signed.len(), HEADER_LENGTH + SIGNATURE_LENGTH);
+        assert_eq!(unwrap_signed(&signed, Some(b"key")).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_signature_mismatch() {
+        let signed = wrap_with(b"tamper evident", Transforms::default(), Some(b"key"), DEFAULT_LEVEL).unwrap();
+        let mismatch = |data: &[u8], key: &[u8]| {
+            matches!(
+                unwrap_signed(data, Some(key)).unwrap_err().downcast_ref(),
+                Some(EnvelopeError::SignatureMismatch)
+            )
+        };
+        assert!(mismatch(&signed, b"wrong key"));
+        let mut tampered = signed.clone();
+        tampered[HEADER_LENGTH] ^= 1;
+        assert!(mismatch(&tampered, b"key"));
+        // so is quietly turning a transform on
+        let mut reflagged = signed.clone();
+        reflagged[5] |= BASE64_BIT;
+        assert!(mismatch(&reflagged, b"key"));
+
+        let unsigned = wrap(b"plain", Transforms { base64: true, ..Transforms::default() }).unwrap();
+        for data in [&unsigned[..], b"not wrapped"] {
+            let error = unwrap_signed(data, Some(b"key")).unwrap_err();
+            assert!(matches!(error.downcast_ref(), Some(EnvelopeError::NotSigned)));
+        }
+        let error = unwrap(b"PNGM\x01\x08short").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EnvelopeError::SignatureTruncated)));
+    }
+}
@@ -0,0 +1,12 @@
+pub mod args;
+pub mod chunk;
+pub mod chunk_type;
+pub mod commands;
+pub mod config;
+pub mod envelope;
+pub mod metadata;
+pub mod png;
+pub mod progress;
+pub mod repl;
+pub mod validation;
+pub mod watch;
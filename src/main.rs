@@ -1,75 +1,767 @@
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod png;
-
+use std::env;
+use std::io::{self, IsTerminal, Write};
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{bail, Result};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use env_logger::WriteStyle;
+use log::LevelFilter;
+use owo_colors::OwoColorize;
+use pngme::args::{self, ColorChoice, DecodeArgs, EncodeArgs, OutputFormat, PrintArgs, WriteArgs};
+use pngme::chunk::ChunkError;
+use pngme::commands;
+use pngme::config::Config;
+use pngme::png::{Png, PngError};
+use pngme::progress;
+use pngme::watch;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Parse and apply changes in memory but don't write any file
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Print results as JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    /// Log more detail, -v for info and -vv for debug
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print nothing when a command that rewrites a file succeeds, errors still go to stderr
+    #[arg(long, global = true)]
+    quiet_success: bool,
+    /// When to color errors, warnings and chunk types
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Refuse to load files larger than this, e.g. 64M or 1G
+    #[arg(long, global = true, value_name = "SIZE", value_parser = args::parse_size, default_value = "256M")]
+    max_size: u64,
+    // chunk_type from pngme.toml, for encode, decode and remove when none is given
+    #[arg(skip)]
+    default_chunk_type: Option<String>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     Encode {
         file_path: PathBuf,
-        chunk_type: String,
-        message: String,
+        /// Left out when --type-hex is given or pngme.toml sets chunk_type
+        #[arg(conflicts_with = "type_hex")]
+        chunk_type: Option<String>,
+        message: Option<String>,
+        /// The message, for when no chunk type comes before it, e.g. with --type-hex
+        #[arg(short, long = "message", value_name = "MESSAGE", conflicts_with = "message")]
+        message_option: Option<String>,
+        /// The chunk type as 8 hex digits instead of text
+        #[arg(long)]
+        type_hex: Option<String>,
         #[arg(short, long)]
-        output: Option<String>,
+        output: Option<PathBuf>,
+        /// Accept any 4 ASCII characters as the chunk type, not just letters
+        #[arg(long)]
+        allow_nonstandard: bool,
+        /// Allow a critical chunk type (uppercase first letter), which can break decoders
+        #[arg(long)]
+        allow_critical: bool,
+        /// Skip the checks that the chunk type is ancillary and safe to copy
+        #[arg(long)]
+        no_check: bool,
+        #[command(flatten)]
+        options: EncodeArgs,
+        /// Keep running and encode again every time the file changes, pair with --replace
+        #[arg(long)]
+        watch: bool,
+        #[command(flatten)]
+        write: WriteArgs,
     },
     Decode {
         file_path: PathBuf,
-        chunk_type: String,
+        /// Defaults to chunk_type from pngme.toml
+        chunk_type: Option<String>,
+        /// The chunk type as 8 hex digits instead of text
+        #[arg(long, conflicts_with = "chunk_type")]
+        type_hex: Option<String>,
+        #[command(flatten)]
+        options: DecodeArgs,
     },
     Remove {
         file_path: PathBuf,
-        chunk_type: String,
+        /// Defaults to chunk_type from pngme.toml
+        chunk_type: Option<String>,
+        /// The chunk type as 8 hex digits instead of text
+        #[arg(long, conflicts_with = "chunk_type")]
+        type_hex: Option<String>,
+        #[command(flatten)]
+        write: WriteArgs,
     },
+    /// Show the chunks of one or more files, a quoted glob like "images/*.png" also works
     Print {
+        #[arg(required = true)]
+        file_paths: Vec<PathBuf>,
+        #[command(flatten)]
+        options: PrintArgs,
+    },
+    /// Summarize the image: dimensions, color, palette, gamma, animation, physical size and
+    /// last modification time
+    Info {
+        file_path: PathBuf,
+        /// Show sizes as exact byte counts instead of e.g. 1.4 MiB
+        #[arg(long)]
+        bytes: bool,
+    },
+    /// List each distinct chunk type in the file with a short description
+    ListTypes {
+        file_path: PathBuf,
+    },
+    /// Report structural problems without modifying the files, globs are expanded
+    Validate {
+        #[arg(required = true)]
+        file_paths: Vec<PathBuf>,
+        /// Also fail unless IHDR is the first chunk and IEND the last
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Count the chunks in the file, or only those of one type
+    Count {
+        file_path: PathBuf,
+        chunk_type: Option<String>,
+    },
+    /// Search every chunk's data for a string and report where it occurs
+    Grep {
+        file_path: PathBuf,
+        pattern: String,
+        /// Read the pattern as hex bytes, e.g. "de ad be ef"
+        #[arg(long)]
+        hex: bool,
+    },
+    /// Report how many bytes fit in the lowest bits of the pixel samples
+    LsbCapacity {
+        file_path: PathBuf,
+    },
+    /// Compare two files chunk by chunk
+    Diff {
+        first_path: PathBuf,
+        second_path: PathBuf,
+    },
+    /// Remove every ancillary chunk, keeping only the critical ones
+    Strip {
+        file_path: PathBuf,
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Strip every .png file under the directory FILE_PATH in place
+        #[arg(short, long, conflicts_with = "output")]
+        recursive: bool,
+        /// With --recursive, finish with a tally of modified, skipped and failed files on stderr
+        #[arg(long, requires = "recursive")]
+        summary: bool,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Print the SHA-256 of the file as pngme would write it
+    Hash {
+        file_path: PathBuf,
+        /// Ignore anything after IEND
+        #[arg(long)]
+        normalize: bool,
+    },
+    /// List every chunk's stored and recomputed CRC, failing if any differ
+    ChecksumReport {
+        file_path: PathBuf,
+    },
+    /// Report how much message data chunks of a type can hold and already hold
+    Capacity {
+        file_path: PathBuf,
+        chunk_type: String,
+    },
+    /// Copy chunks from one file into another, before its IEND
+    Merge {
+        /// A PNG, or a JSON file written by export or split
+        source_path: PathBuf,
+        destination_path: PathBuf,
+        /// Only copy chunks of this type, by default every ancillary chunk is copied
+        #[arg(long = "type")]
+        chunk_type: Option<String>,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Write the raw data of the first chunk of a type to a file
+    Extract {
+        file_path: PathBuf,
+        chunk_type: String,
+        output_path: PathBuf,
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Write every chunk to a JSON file as its type and base64 data
+    Export {
+        file_path: PathBuf,
+        output_path: PathBuf,
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Build a PNG from a JSON file written by export, recomputing lengths and CRCs
+    Import {
+        json_path: PathBuf,
+        output_path: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Write the critical chunks to a minimal image and the ancillary ones to a JSON sidecar
+    Split {
+        file_path: PathBuf,
+        image_path: PathBuf,
+        /// In the export format, merge can copy these chunks back in
+        sidecar_path: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Write the decompressed ICC profile from the iCCP chunk to a file
+    ExtractIcc {
+        file_path: PathBuf,
+        output_path: PathBuf,
+        /// Overwrite the output file if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Add a chunk holding the raw bytes of a file, before IEND
+    Inject {
         file_path: PathBuf,
+        chunk_type: String,
+        #[arg(required_unless_present = "data_hex")]
+        payload_path: Option<PathBuf>,
+        /// Take the chunk data from hex digits on the command line instead of a file
+        #[arg(long, value_name = "HEX", conflicts_with = "payload_path")]
+        data_hex: Option<String>,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Remove any bytes after the IEND chunk
+    StripTrailing {
+        file_path: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Append an empty IEND chunk when the file has none
+    FixEnd {
+        file_path: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Set the tIME chunk to the current UTC time, replacing any existing one
+    Touch {
+        file_path: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Set the tEXt value for KEYWORD, replacing an existing entry or adding one before IEND
+    ReplaceText {
+        file_path: PathBuf,
+        keyword: String,
+        text: String,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Remove chunks identical to an earlier one, keeping the first
+    Dedup {
+        file_path: PathBuf,
+        /// Also collapse identical IDAT chunks, which are normally left alone
+        #[arg(long)]
+        include_idat: bool,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Change the type of every chunk of one type to another, keeping the data
+    RenameType {
+        file_path: PathBuf,
+        from: String,
+        to: String,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Move the first chunk of a type to a new index, --allow-ends allows moving IHDR or IEND
+    Reorder {
+        file_path: PathBuf,
+        #[arg(long = "type", value_name = "TYPE")]
+        chunk_type: String,
+        /// Where the chunk ends up, 0 is the first chunk
+        #[arg(long, value_name = "INDEX")]
+        to_index: usize,
+        /// Allow a move that takes IHDR off the front or IEND off the end
+        #[arg(long)]
+        allow_ends: bool,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Set the property bits of every chunk of a type, recomputing their CRCs
+    SetBits {
+        file_path: PathBuf,
+        chunk_type: String,
+        #[arg(long)]
+        ancillary: Option<bool>,
+        #[arg(long)]
+        safe_to_copy: Option<bool>,
+        #[command(flatten)]
+        write: WriteArgs,
     },
+    /// Load a file once and edit it interactively, changes are only written on save
+    Repl {
+        file_path: PathBuf,
+        #[command(flatten)]
+        write: WriteArgs,
+    },
+    /// Print a shell completion script to stdout
+    Completions {
+        shell: Shell,
+    },
+}
+
+impl Commands {
+    // the commands that rewrite a file
+    fn write_args_mut(&mut self) -> Option<&mut WriteArgs> {
+        match self {
+            Commands::Encode { write, .. }
+            | Commands::Remove { write, .. }
+            | Commands::Strip { write, .. }
+            | Commands::Merge { write, .. }
+            | Commands::Inject { write, .. }
+            | Commands::Import { write, .. }
+            | Commands::Split { write, .. }
+            | Commands::StripTrailing { write, .. }
+            | Commands::FixEnd { write, .. }
+            | Commands::Dedup { write, .. }
+            | Commands::Touch { write, .. }
+            | Commands::ReplaceText { write, .. }
+            | Commands::RenameType { write, .. }
+            | Commands::Reorder { write, .. }
+            | Commands::SetBits { write, .. }
+            | Commands::Repl { write, .. } => Some(write),
+            _ => None,
+        }
+    }
+}
+
+// config values only fill in what wasn't given on the command line
+fn apply_config(cli: &mut Cli, matches: &ArgMatches, config: &Config) -> Result<()> {
+    if matches.value_source("max_size") != Some(ValueSource::CommandLine) {
+        if let Some(max_size) = config.max_size()? {
+            cli.max_size = max_size;
+        }
+    }
+    if config.format == Some(OutputFormat::Json) {
+        cli.json = true;
+    }
+    cli.default_chunk_type = config.chunk_type.clone();
+    if let Some(write) = cli.command.write_args_mut() {
+        if write.backup.is_none() {
+            write.backup = config.backup_suffix();
+        }
+    }
+    Ok(())
+}
+
+// the configured default only applies when neither a chunk type nor --type-hex was given
+fn chunk_type_or_default<'a>(
+    chunk_type: Option<&'a str>,
+    type_hex: Option<&str>,
+    default: Option<&'a str>,
+) -> Option<&'a str> {
+    match (chunk_type, type_hex) {
+        (None, None) => default,
+        _ => chunk_type,
+    }
+}
+
+fn write_completions(shell: Shell, out: &mut impl Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "pngme", out);
 }
 
-fn main() {
-    let cli = Cli::parse();
+// decided once the flags are parsed, errors before that are printed plain
+static STDERR_COLOR: AtomicBool = AtomicBool::new(false);
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            if STDERR_COLOR.load(Ordering::Relaxed) {
+                eprintln!("{}: {error:?}", "Error".red());
+            } else {
+                eprintln!("Error: {error:?}");
+            }
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
+
+// lets scripts tell failures apart, the first recognised error in the chain decides
+fn exit_code(error: &anyhow::Error) -> u8 {
+    for cause in error.chain() {
+        if let Some(PngError::IncorrectPNGSignature) = cause.downcast_ref() {
+            return 2;
+        }
+        if let Some(ChunkError::InvalidCrc { .. }) = cause.downcast_ref() {
+            return 3;
+        }
+        if let Some(PngError::ChunkNotFound(_)) = cause.downcast_ref() {
+            return 4;
+        }
+        if cause.downcast_ref::<io::Error>().is_some() {
+            return 5;
+        }
+    }
+    1
+}
+
+fn run() -> Result<()> {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    apply_config(&mut cli, &matches, &Config::load()?)?;
+
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Info,
+        (false, _) => LevelFilter::Debug,
+    };
+    let no_color = env::var_os("NO_COLOR");
+    let stderr_color = cli.color.enabled(io::stderr().is_terminal(), no_color.as_deref());
+    STDERR_COLOR.store(stderr_color, Ordering::Relaxed);
+    // env_logger already paints WARN yellow and ERROR red, it just needs telling when
+    let style = if stderr_color { WriteStyle::Always } else { WriteStyle::Never };
+    env_logger::Builder::new().filter_level(level).write_style(style).init();
+    if let Commands::Print { options, .. } = &mut cli.command {
+        options.color = cli.color.enabled(io::stdout().is_terminal(), no_color.as_deref());
+    }
+
+    Png::set_max_file_size(cli.max_size);
+    progress::set_enabled(!cli.quiet);
+    let format = if cli.json { OutputFormat::Json } else { OutputFormat::Text };
+
+    if let Some(write) = cli.command.write_args_mut() {
+        write.dry_run = cli.dry_run;
+        write.format = format;
+        write.quiet_success = cli.quiet_success;
+    }
+
     match &cli.command {
         Commands::Encode {
             file_path,
             chunk_type,
             message,
+            message_option,
+            type_hex,
             output,
+            allow_nonstandard,
+            allow_critical,
+            no_check,
+            options,
+            watch,
+            write,
         } => {
-            println!("Encoding message into {}", file_path.display());
-            println!("Chunk type: {}", chunk_type);
-            println!("Message: {}", message);
-            if let Some(out) = output {
-                println!("Output file: {}", out);
+            let message = message.as_deref().or(message_option.as_deref());
+            // --append-file on its own only copies chunks in
+            if let (None, None, None, Some(source)) =
+                (type_hex, chunk_type, message, &options.append_file)
+            {
+                return commands::encode_append_only(file_path, source, output.as_deref(), write);
+            }
+            let default = cli.default_chunk_type.as_deref();
+            let (chunk_type, message) = match (chunk_type.as_deref(), message) {
+                (chunk_type, Some(message)) => (chunk_type, message),
+                // just a message, the chunk type comes from the config
+                (Some(message), None) if default.is_some() => (None, message),
+                _ => bail!("Encode needs a chunk type (or --type-hex) and a message"),
+            };
+            let chunk_type = chunk_type_or_default(chunk_type, type_hex.as_deref(), default);
+            let chunk_type =
+                commands::parse_chunk_type(chunk_type, type_hex.as_deref(), *allow_nonstandard)?;
+            commands::check_encode_type(&chunk_type, *allow_critical, *no_check)?;
+            commands::encode(file_path, &chunk_type, message, options, output.as_deref(), write)?;
+            if *watch {
+                watch::watch(file_path, watch::DEBOUNCE, || {
+                    commands::encode(file_path, &chunk_type, message, options, output.as_deref(), write)
+                })?;
             }
         }
         Commands::Decode {
             file_path,
             chunk_type,
+            type_hex,
+            options,
         } => {
-            println!("Decoding message from {}", file_path.display());
-            println!("Chunk type: {}", chunk_type);
+            let default = cli.default_chunk_type.as_deref();
+            let chunk_type = chunk_type_or_default(chunk_type.as_deref(), type_hex.as_deref(), default);
+            let chunk_type = commands::parse_chunk_type(chunk_type, type_hex.as_deref(), false)?;
+            commands::decode(file_path, &chunk_type, options, format)?
         }
         Commands::Remove {
             file_path,
             chunk_type,
+            type_hex,
+            write,
         } => {
-            println!("Removing chunk from {}", file_path.display());
-            println!("Chunk type: {}", chunk_type);
+            let default = cli.default_chunk_type.as_deref();
+            let chunk_type = chunk_type_or_default(chunk_type.as_deref(), type_hex.as_deref(), default);
+            let chunk_type = commands::parse_chunk_type(chunk_type, type_hex.as_deref(), false)?;
+            commands::remove(file_path, &chunk_type, write)?
+        }
+        Commands::Print { file_paths, options } => commands::print(file_paths, options, format)?,
+        Commands::Info { file_path, bytes } => commands::info(file_path, *bytes, format)?,
+        Commands::ListTypes { file_path } => commands::list_types(file_path, format)?,
+        Commands::Validate { file_paths, strict } => {
+            commands::validate(file_paths, *strict, format)?
         }
-        Commands::Print { file_path } => {
-            println!("Printing chunks from {}", file_path.display());
+        Commands::Count {
+            file_path,
+            chunk_type,
+        } => commands::count(file_path, chunk_type.as_deref(), format)?,
+        Commands::Grep {
+            file_path,
+            pattern,
+            hex,
+        } => commands::grep(file_path, pattern, *hex, format)?,
+        Commands::LsbCapacity { file_path } => commands::lsb_capacity(file_path, format)?,
+        Commands::Diff {
+            first_path,
+            second_path,
+        } => commands::diff(first_path, second_path, format)?,
+        Commands::Strip {
+            file_path,
+            recursive: true,
+            summary,
+            write,
+            ..
+        } => commands::strip_recursive(file_path, *summary, write)?,
+        Commands::Strip {
+            file_path,
+            output,
+            write,
+            ..
+        } => commands::strip(file_path, output.as_deref(), write)?,
+        Commands::Hash {
+            file_path,
+            normalize,
+        } => commands::hash(file_path, *normalize, format)?,
+        Commands::ChecksumReport { file_path } => commands::checksum_report(file_path, format)?,
+        Commands::Capacity {
+            file_path,
+            chunk_type,
+        } => commands::capacity(file_path, chunk_type, format)?,
+        Commands::Merge {
+            source_path,
+            destination_path,
+            chunk_type,
+            write,
+        } => commands::merge(source_path, destination_path, chunk_type.as_deref(), write)?,
+        Commands::Extract {
+            file_path,
+            chunk_type,
+            output_path,
+            force,
+        } => commands::extract(file_path, chunk_type, output_path, *force, format)?,
+        Commands::Export {
+            file_path,
+            output_path,
+            force,
+        } => commands::export(file_path, output_path, *force, format)?,
+        Commands::Import {
+            json_path,
+            output_path,
+            write,
+        } => commands::import(json_path, output_path, write)?,
+        Commands::Split {
+            file_path,
+            image_path,
+            sidecar_path,
+            write,
+        } => commands::split(file_path, image_path, sidecar_path, write)?,
+        Commands::ExtractIcc {
+            file_path,
+            output_path,
+            force,
+        } => commands::extract_icc(file_path, output_path, *force, format)?,
+        Commands::Inject {
+            file_path,
+            chunk_type,
+            payload_path,
+            data_hex,
+            write,
+        } => commands::inject(
+            file_path,
+            chunk_type,
+            payload_path.as_deref(),
+            data_hex.as_deref(),
+            write,
+        )?,
+        Commands::StripTrailing { file_path, write } => commands::strip_trailing(file_path, write)?,
+        Commands::FixEnd { file_path, write } => commands::fix_end(file_path, write)?,
+        Commands::Touch { file_path, write } => commands::touch(file_path, write)?,
+        Commands::ReplaceText {
+            file_path,
+            keyword,
+            text,
+            write,
+        } => commands::replace_text(file_path, keyword, text, write)?,
+        Commands::Dedup {
+            file_path,
+            include_idat,
+            write,
+        } => commands::dedup(file_path, *include_idat, write)?,
+        Commands::Reorder {
+            file_path,
+            chunk_type,
+            to_index,
+            allow_ends,
+            write,
+        } => commands::reorder(file_path, chunk_type, *to_index, *allow_ends, write)?,
+        Commands::RenameType {
+            file_path,
+            from,
+            to,
+            write,
+        } => commands::rename_type(file_path, from, to, write)?,
+        Commands::SetBits {
+            file_path,
+            chunk_type,
+            ancillary,
+            safe_to_copy,
+            write,
+        } => commands::set_bits(file_path, chunk_type, *ancillary, *safe_to_copy, write)?,
+        Commands::Repl { file_path, write } => commands::repl(file_path, write)?,
+        Commands::Completions { shell } => write_completions(*shell, &mut io::stdout()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        use anyhow::{anyhow, Context};
+
+        assert_eq!(exit_code(&PngError::IncorrectPNGSignature.into()), 2);
+        let crc = ChunkError::InvalidCrc { expected: 1, calculated: 2 };
+        assert_eq!(exit_code(&crc.into()), 3);
+        assert_eq!(exit_code(&PngError::ChunkNotFound("ruSt".to_string()).into()), 4);
+        let missing: Result<()> = Err(io::Error::from(io::ErrorKind::NotFound).into());
+        assert_eq!(exit_code(&missing.context("Failed to read a.png").unwrap_err()), 5);
+        assert_eq!(exit_code(&anyhow!("something else")), 1);
+        assert_eq!(exit_code(&ChunkError::LengthTooLarge(usize::MAX).into()), 1);
+    }
+
+    #[test]
+    fn test_color_choice() {
+        let no_color = Some(std::ffi::OsStr::new("1"));
+        assert!(ColorChoice::Auto.enabled(true, None));
+        assert!(!ColorChoice::Auto.enabled(false, None));
+        assert!(!ColorChoice::Auto.enabled(true, no_color));
+        assert!(ColorChoice::Auto.enabled(true, Some(std::ffi::OsStr::new(""))));
+        assert!(!ColorChoice::Never.enabled(true, None));
+        assert!(ColorChoice::Always.enabled(false, no_color));
+
+        let cli = Cli::try_parse_from(["pngme", "print", "a.png", "--color", "never"]).unwrap();
+        assert_eq!(cli.color, ColorChoice::Never);
+        assert!(Cli::try_parse_from(["pngme", "--color", "sometimes", "print", "a.png"]).is_err());
+    }
+
+    #[test]
+    fn test_deflate_level_range() {
+        let parse = |args: &[&str]| -> Result<u32, clap::Error> {
+            let cli = Cli::try_parse_from([&["pngme", "encode", "a.png", "ruSt", "hi"], args].concat())?;
+            match cli.command {
+                Commands::Encode { options, .. } => Ok(options.deflate_level),
+                _ => unreachable!(),
+            }
+        };
+        assert_eq!(parse(&["--compress"]).unwrap(), 6);
+        assert_eq!(parse(&["--compress", "--deflate-level", "0"]).unwrap(), 0);
+        assert_eq!(parse(&["--compress", "--deflate-level", "9"]).unwrap(), 9);
+        assert!(parse(&["--compress", "--deflate-level", "10"]).is_err());
+        // the level means nothing without --compress
+        assert!(parse(&["--deflate-level", "9"]).is_err());
+    }
+
+    #[test]
+    fn test_encode_type_hex_takes_the_message_as_a_flag() {
+        let cli = Cli::try_parse_from(["pngme", "encode", "f.png", "--type-hex", "52755374", "-m", "hi"]);
+        let Commands::Encode { chunk_type, message, message_option, .. } = cli.unwrap().command else {
+            unreachable!()
+        };
+        assert_eq!((chunk_type, message, message_option.as_deref()), (None, None, Some("hi")));
+
+        // a positional chunk type can't be combined with --type-hex
+        assert!(Cli::try_parse_from(["pngme", "encode", "f.png", "--type-hex", "52755374", "RuSt", "hi"]).is_err());
+        assert!(Cli::try_parse_from(["pngme", "encode", "f.png", "--type-hex", "52755374", "hi"]).is_err());
+        assert!(Cli::try_parse_from(["pngme", "encode", "f.png", "ruSt", "hi", "-m", "again"]).is_err());
+    }
+
+    fn parse_with_config(args: &[&str], config: &str) -> Cli {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        apply_config(&mut cli, &matches, &Config::parse(config).unwrap()).unwrap();
+        cli
+    }
+
+    fn backup(cli: &mut Cli) -> Option<String> {
+        cli.command.write_args_mut().unwrap().backup.clone()
+    }
+
+    #[test]
+    fn test_cli_flags_override_config() {
+        let config = r#"
+            chunk_type = "ruSt"
+            backup = ".cfg"
+            max_size = "64M"
+        "#;
+
+        let mut cli = parse_with_config(&["pngme", "remove", "a.png"], config);
+        assert_eq!(cli.max_size, 64 * 1024 * 1024);
+        assert_eq!(backup(&mut cli).as_deref(), Some(".cfg"));
+        assert_eq!(chunk_type_or_default(None, None, cli.default_chunk_type.as_deref()), Some("ruSt"));
+
+        let args = ["pngme", "--max-size", "1M", "remove", "a.png", "abCd", "--backup=.cli"];
+        let mut cli = parse_with_config(&args, config);
+        assert_eq!(cli.max_size, 1024 * 1024);
+        assert_eq!(backup(&mut cli).as_deref(), Some(".cli"));
+        let default = cli.default_chunk_type.as_deref();
+        assert_eq!(chunk_type_or_default(Some("abCd"), None, default), Some("abCd"));
+        assert_eq!(chunk_type_or_default(None, Some("72755374"), default), None);
+
+        // a size on the command line wins even when it matches the built in default
+        let cli = parse_with_config(&["pngme", "--max-size", "256M", "info", "a.png"], config);
+        assert_eq!(cli.max_size, Png::DEFAULT_MAX_FILE_SIZE);
+    }
+
+    #[test]
+    fn test_no_config_keeps_cli_defaults() {
+        let mut cli = parse_with_config(&["pngme", "remove", "a.png", "ruSt"], "");
+        assert_eq!(cli.max_size, Png::DEFAULT_MAX_FILE_SIZE);
+        assert!(!cli.json);
+        assert_eq!(backup(&mut cli), None);
+
+        let cli = parse_with_config(&["pngme", "info", "a.png"], "format = \"json\"");
+        assert!(cli.json);
+    }
+
+    #[test]
+    fn test_completions_for_each_shell() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut out = Vec::new();
+            write_completions(shell, &mut out);
+            let script = String::from_utf8(out).unwrap();
+            assert!(script.contains("list-types"), "no subcommands in {shell} completions");
         }
     }
 }
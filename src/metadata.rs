@@ -0,0 +1,609 @@
+use std::fmt::{Display, Formatter};
+use std::io::Read;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use flate2::read::ZlibDecoder;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+
+// fails unless the chunk is of the expected type and holds exactly `length` bytes
+fn fixed_payload<'a>(chunk: &'a Chunk, chunk_type: &str, length: usize) -> Result<&'a [u8]> {
+    if !chunk.is_type(chunk_type) {
+        bail!("Expected a {chunk_type} chunk, got {}", chunk.chunk_type());
+    }
+    if chunk.data().len() != length {
+        bail!(
+            "{chunk_type} chunk should hold {length} bytes, found {}",
+            chunk.data().len()
+        );
+    }
+    Ok(chunk.data())
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes[..4].try_into().expect("caller passes at least 4 bytes"))
+}
+
+// the fixed 13 byte IHDR payload
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression: u8,
+    pub filter: u8,
+    pub interlace: u8,
+}
+
+impl ImageHeader {
+    pub fn from_chunk(chunk: &Chunk) -> Result<ImageHeader> {
+        let data = fixed_payload(chunk, "IHDR", 13)?;
+        Ok(ImageHeader {
+            width: be_u32(&data[0..4]),
+            height: be_u32(&data[4..8]),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression: data[10],
+            filter: data[11],
+            interlace: data[12],
+        })
+    }
+
+    // samples per pixel, None for a color type the spec doesn't define
+    pub fn channels(&self) -> Option<u8> {
+        match self.color_type {
+            0 | 3 => Some(1),
+            4 => Some(2),
+            2 => Some(3),
+            6 => Some(4),
+            _ => None,
+        }
+    }
+
+    pub fn color_name(&self) -> Option<&'static str> {
+        match self.color_type {
+            0 => Some("greyscale"),
+            2 => Some("truecolour"),
+            3 => Some("indexed"),
+            4 => Some("greyscale with alpha"),
+            6 => Some("truecolour with alpha"),
+            _ => None,
+        }
+    }
+
+    // the spec's table of bit depths each color type permits
+    pub fn allowed_bit_depths(&self) -> Option<&'static [u8]> {
+        match self.color_type {
+            0 => Some(&[1, 2, 4, 8, 16]),
+            3 => Some(&[1, 2, 4, 8]),
+            2 | 4 | 6 => Some(&[8, 16]),
+            _ => None,
+        }
+    }
+
+    // the color type's name when the bit depth is legal for it
+    pub fn check_color(&self) -> Result<&'static str> {
+        let (Some(name), Some(allowed)) = (self.color_name(), self.allowed_bit_depths()) else {
+            bail!("IHDR color type {} isn't defined by the PNG spec", self.color_type);
+        };
+        if !allowed.contains(&self.bit_depth) {
+            let allowed: Vec<String> = allowed.iter().map(u8::to_string).collect();
+            bail!(
+                "Bit depth {} isn't allowed for color type {} ({name}), expected one of {}",
+                self.bit_depth,
+                self.color_type,
+                allowed.join(", ")
+            );
+        }
+        Ok(name)
+    }
+
+    // every sample of every pixel, independent of bit depth and interlacing
+    pub fn samples(&self) -> Option<u64> {
+        let channels = u64::from(self.channels()?);
+        Some(u64::from(self.width) * u64::from(self.height) * channels)
+    }
+
+    // the decompressed IDAT stream of a non-interlaced image, a filter byte in front of
+    // each row of packed samples
+    pub fn filtered_length(&self) -> Option<u64> {
+        if self.interlace != 0 {
+            return None;
+        }
+        let row_bits = u64::from(self.width) * u64::from(self.channels()?) * u64::from(self.bit_depth);
+        Some(u64::from(self.height) * (1 + row_bits.div_ceil(8)))
+    }
+}
+
+// PLTE, the RGB entries an indexed image's pixels point into
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    const MAX_ENTRIES: usize = 256;
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Palette> {
+        if !chunk.is_type("PLTE") {
+            bail!("Expected a PLTE chunk, got {}", chunk.chunk_type());
+        }
+        let data = chunk.data();
+        if !data.len().is_multiple_of(3) {
+            bail!("PLTE length {} isn't a multiple of 3", data.len());
+        }
+        let entries = data.len() / 3;
+        if !(1..=Self::MAX_ENTRIES).contains(&entries) {
+            bail!("PLTE holds {entries} entries, expected 1 to {}", Self::MAX_ENTRIES);
+        }
+        let colors = data.chunks_exact(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect();
+        Ok(Palette { colors })
+    }
+
+    // the first `count` entries as #rrggbb
+    pub fn hex_colors(&self, count: usize) -> Vec<String> {
+        self.colors
+            .iter()
+            .take(count)
+            .map(|[r, g, b]| format!("#{r:02x}{g:02x}{b:02x}"))
+            .collect()
+    }
+}
+
+// acTL, which marks an animated PNG. A play count of 0 means loop forever
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    pub fn from_chunk(chunk: &Chunk) -> Result<AnimationControl> {
+        let data = fixed_payload(chunk, "acTL", 8)?;
+        let control = AnimationControl {
+            num_frames: be_u32(&data[0..4]),
+            num_plays: be_u32(&data[4..8]),
+        };
+        if control.num_frames == 0 {
+            bail!("acTL declares 0 frames, an animation needs at least 1");
+        }
+        Ok(control)
+    }
+}
+
+impl Display for AnimationControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.num_plays {
+            0 => write!(f, "{} frames, looping forever", self.num_frames),
+            1 => write!(f, "{} frames, played once", self.num_frames),
+            plays => write!(f, "{} frames, played {plays} times", self.num_frames),
+        }
+    }
+}
+
+// gAMA stores the image gamma times 100000, 45455 for the usual 1/2.2
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Gamma {
+    pub scaled: u32,
+}
+
+impl Gamma {
+    const SCALE: f64 = 100_000.0;
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<Gamma> {
+        let data = fixed_payload(chunk, "gAMA", 4)?;
+        Ok(Gamma { scaled: be_u32(data) })
+    }
+
+    pub fn value(&self) -> f64 {
+        f64::from(self.scaled) / Self::SCALE
+    }
+}
+
+impl Display for Gamma {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.5}", self.value())
+    }
+}
+
+// pHYs, pixels per unit on each axis. Unit 1 is the metre, 0 means the values only give
+// the pixel aspect ratio
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PhysicalDimensions {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: u8,
+}
+
+impl PhysicalDimensions {
+    const METRE: u8 = 1;
+    const INCHES_PER_METRE: f64 = 39.3701;
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<PhysicalDimensions> {
+        let data = fixed_payload(chunk, "pHYs", 9)?;
+        Ok(PhysicalDimensions {
+            pixels_per_unit_x: be_u32(&data[0..4]),
+            pixels_per_unit_y: be_u32(&data[4..8]),
+            unit: data[8],
+        })
+    }
+
+    // only known when the unit is the metre
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        (self.unit == Self::METRE).then(|| {
+            (
+                f64::from(self.pixels_per_unit_x) / Self::INCHES_PER_METRE,
+                f64::from(self.pixels_per_unit_y) / Self::INCHES_PER_METRE,
+            )
+        })
+    }
+}
+
+impl Display for PhysicalDimensions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (x, y) = (self.pixels_per_unit_x, self.pixels_per_unit_y);
+        match (self.dpi(), self.unit) {
+            (Some((dpi_x, dpi_y)), _) => {
+                write!(f, "{x} x {y} pixels per metre ({dpi_x:.0} x {dpi_y:.0} DPI)")
+            }
+            (None, 0) => write!(f, "{x} x {y} (aspect ratio only)"),
+            (None, unit) => write!(f, "{x} x {y} pixels per unknown unit {unit}"),
+        }
+    }
+}
+
+// tIME, always UTC. A leap second is allowed, as the spec does
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LastModified {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl LastModified {
+    pub fn from_chunk(chunk: &Chunk) -> Result<LastModified> {
+        let data = fixed_payload(chunk, "tIME", 7)?;
+        let time = LastModified {
+            year: u16::from_be_bytes([data[0], data[1]]),
+            month: data[2],
+            day: data[3],
+            hour: data[4],
+            minute: data[5],
+            second: data[6],
+        };
+
+        let fields = [
+            ("month", time.month, 1, 12),
+            ("day", time.day, 1, 31),
+            ("hour", time.hour, 0, 23),
+            ("minute", time.minute, 0, 59),
+            ("second", time.second, 0, 60),
+        ];
+        for (name, value, min, max) in fields {
+            if !(min..=max).contains(&value) {
+                bail!("tIME {name} {value} is out of range, expected {min} to {max}");
+            }
+        }
+        Ok(time)
+    }
+
+    // the year has to fit the spec's two bytes
+    pub fn from_datetime(time: DateTime<Utc>) -> Result<LastModified> {
+        Ok(LastModified {
+            year: u16::try_from(time.year()).context("Year doesn't fit in a tIME chunk")?,
+            month: time.month() as u8,
+            day: time.day() as u8,
+            hour: time.hour() as u8,
+            minute: time.minute() as u8,
+            second: time.second() as u8,
+        })
+    }
+
+    pub fn now() -> Result<LastModified> {
+        LastModified::from_datetime(Utc::now())
+    }
+
+    // None for a date that doesn't exist, like February 30th. A leap second reads as :59
+    pub fn to_datetime(&self) -> Option<DateTime<Utc>> {
+        NaiveDate::from_ymd_opt(self.year.into(), self.month.into(), self.day.into())?
+            .and_hms_opt(self.hour.into(), self.minute.into(), self.second.min(59).into())
+            .map(|time| time.and_utc())
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend([self.month, self.day, self.hour, self.minute, self.second]);
+        Chunk::new(ChunkType::TIME, data)
+    }
+}
+
+impl Display for LastModified {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+// iCCP: a Latin-1 profile name ended by a NUL, a compression method byte and the
+// zlib compressed profile. The profile is kept decompressed
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IccProfile {
+    pub name: String,
+    pub profile: Vec<u8>,
+}
+
+impl IccProfile {
+    pub fn from_chunk(chunk: &Chunk) -> Result<IccProfile> {
+        if !chunk.is_type("iCCP") {
+            bail!("Expected a iCCP chunk, got {}", chunk.chunk_type());
+        }
+        let data = chunk.data();
+        let name_end = data
+            .iter()
+            .position(|&byte| byte == 0)
+            .context("iCCP chunk has no NUL after the profile name")?;
+        let name = data[..name_end].iter().map(|&byte| char::from(byte)).collect();
+
+        match data.get(name_end + 1) {
+            Some(0) => {}
+            Some(method) => bail!("iCCP compression method {method} is unknown, only 0 (zlib) is"),
+            None => bail!("iCCP chunk ends before the compression method"),
+        }
+        let mut profile = Vec::new();
+        ZlibDecoder::new(&data[name_end + 2..])
+            .read_to_end(&mut profile)
+            .context("iCCP profile data is not a valid zlib stream")?;
+        Ok(IccProfile { name, profile })
+    }
+}
+
+// tEXt: a Latin-1 keyword of 1 to 79 bytes, a NUL, then Latin-1 text running to the end of
+// the chunk. Neither part may hold a NUL
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEntry {
+    pub keyword: String,
+    pub text: String,
+}
+
+impl TextEntry {
+    pub fn new(keyword: &str, text: &str) -> Result<TextEntry> {
+        if keyword.is_empty() || keyword.chars().count() > 79 {
+            bail!("tEXt keyword must be 1 to 79 characters, {keyword:?} isn't");
+        }
+        for (part, value) in [("keyword", keyword), ("text", text)] {
+            if value.chars().any(|c| c == '\0' || u32::from(c) > 0xff) {
+                bail!("tEXt {part} {value:?} must be Latin-1 without NUL characters");
+            }
+        }
+        Ok(TextEntry { keyword: keyword.to_string(), text: text.to_string() })
+    }
+
+    pub fn from_chunk(chunk: &Chunk) -> Result<TextEntry> {
+        if !chunk.is_type("tEXt") {
+            bail!("Expected a tEXt chunk, got {}", chunk.chunk_type());
+        }
+        let data = chunk.data();
+        let keyword_end = data
+            .iter()
+            .position(|&byte| byte == 0)
+            .context("tEXt chunk has no NUL after the keyword")?;
+        let latin1 = |bytes: &[u8]| -> String { bytes.iter().map(|&byte| char::from(byte)).collect() };
+        TextEntry::new(&latin1(&data[..keyword_end]), &latin1(&data[keyword_end + 1..]))
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        // new and from_chunk only let Latin-1 through, so every char fits a byte
+        let mut data: Vec<u8> = self.keyword.chars().map(|c| c as u8).collect();
+        data.push(0);
+        data.extend(self.text.chars().map(|c| c as u8));
+        let chunk_type = ChunkType::try_from(*b"tEXt").expect("tEXt should be a valid chunk type");
+        Chunk::new(chunk_type, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_image_header() {
+        let data = [0, 0, 1, 0, 0, 0, 0, 200, 8, 6, 0, 0, 1];
+        let header = ImageHeader::from_chunk(&chunk("IHDR", &data)).unwrap();
+        assert_eq!((header.width, header.height), (256, 200));
+        assert_eq!((header.bit_depth, header.color_type, header.interlace), (8, 6, 1));
+        assert!(ImageHeader::from_chunk(&chunk("IHDR", &data[..12])).is_err());
+    }
+
+    #[test]
+    fn test_image_header_color_check() {
+        let header = |bit_depth, color_type| ImageHeader {
+            width: 1,
+            height: 1,
+            bit_depth,
+            color_type,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        for (bit_depth, color_type) in [(8, 6), (16, 2), (1, 0), (4, 3), (16, 4)] {
+            assert!(header(bit_depth, color_type).check_color().is_ok(), "{bit_depth} {color_type}");
+        }
+        assert_eq!(header(8, 6).check_color().unwrap(), "truecolour with alpha");
+
+        let error = header(16, 3).check_color().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Bit depth 16 isn't allowed for color type 3 (indexed), expected one of 1, 2, 4, 8"
+        );
+        assert!(header(4, 6).check_color().is_err());
+        assert!(header(3, 0).check_color().is_err());
+        assert!(header(8, 5).check_color().is_err());
+    }
+
+    #[test]
+    fn test_image_header_sizes() {
+        let mut header = ImageHeader {
+            width: 50,
+            height: 50,
+            bit_depth: 8,
+            color_type: 6,
+            compression: 0,
+            filter: 0,
+            interlace: 0,
+        };
+        assert_eq!(header.channels(), Some(4));
+        assert_eq!(header.samples(), Some(10_000));
+        assert_eq!(header.filtered_length(), Some(50 * 201));
+
+        // 1 bit greyscale packs 8 pixels to a byte, rows are padded to a whole byte
+        header.color_type = 0;
+        header.bit_depth = 1;
+        assert_eq!(header.filtered_length(), Some(50 * 8));
+        header.interlace = 1;
+        assert_eq!(header.filtered_length(), None);
+        header.color_type = 5;
+        assert_eq!(header.samples(), None);
+    }
+
+    #[test]
+    fn test_palette() {
+        let palette = Palette::from_chunk(&chunk("PLTE", &[0, 0, 0, 0xff, 0x80, 0x00, 0xff, 0xff, 0xff])).unwrap();
+        assert_eq!(palette.colors.len(), 3);
+        assert_eq!(palette.hex_colors(2), ["#000000", "#ff8000"]);
+        assert_eq!(palette.hex_colors(10).len(), 3);
+
+        let error = Palette::from_chunk(&chunk("PLTE", &[0; 10])).unwrap_err();
+        assert_eq!(error.to_string(), "PLTE length 10 isn't a multiple of 3");
+        assert!(Palette::from_chunk(&chunk("PLTE", &[])).is_err());
+        assert!(Palette::from_chunk(&chunk("PLTE", &[0; 3 * 257])).is_err());
+    }
+
+    #[test]
+    fn test_animation_control() {
+        let control = AnimationControl::from_chunk(&chunk("acTL", &[0, 0, 0, 12, 0, 0, 0, 0])).unwrap();
+        assert_eq!(control, AnimationControl { num_frames: 12, num_plays: 0 });
+        assert_eq!(control.to_string(), "12 frames, looping forever");
+        let control = AnimationControl::from_chunk(&chunk("acTL", &[0, 0, 0, 3, 0, 0, 0, 2])).unwrap();
+        assert_eq!(control.to_string(), "3 frames, played 2 times");
+
+        assert!(AnimationControl::from_chunk(&chunk("acTL", &[0, 0, 0, 0, 0, 0, 0, 1])).is_err());
+        assert!(AnimationControl::from_chunk(&chunk("acTL", &[0, 0, 0, 3])).is_err());
+    }
+
+    #[test]
+    fn test_gamma() {
+        let scaled = (100_000.0 / 2.2_f64).round() as u32;
+        let gamma = Gamma::from_chunk(&chunk("gAMA", &scaled.to_be_bytes())).unwrap();
+        assert_eq!(gamma.scaled, 45455);
+        assert_eq!(gamma.to_string(), "0.45455");
+        assert!(Gamma::from_chunk(&chunk("gAMA", &[0, 0, 0xb1])).is_err());
+    }
+
+    #[test]
+    fn test_physical_dimensions_dpi() {
+        // 2835 pixels per metre is the usual encoding of 72 DPI
+        let data = [0, 0, 0x0b, 0x13, 0, 0, 0x0b, 0x13, 1];
+        let phys = PhysicalDimensions::from_chunk(&chunk("pHYs", &data)).unwrap();
+        assert_eq!(phys.pixels_per_unit_x, 2835);
+        let (dpi_x, dpi_y) = phys.dpi().unwrap();
+        assert!((dpi_x - 72.0).abs() < 0.01 && (dpi_y - 72.0).abs() < 0.01);
+        assert_eq!(phys.to_string(), "2835 x 2835 pixels per metre (72 x 72 DPI)");
+    }
+
+    #[test]
+    fn test_physical_dimensions_aspect_only() {
+        let data = [0, 0, 0, 1, 0, 0, 0, 2, 0];
+        let phys = PhysicalDimensions::from_chunk(&chunk("pHYs", &data)).unwrap();
+        assert_eq!(phys.dpi(), None);
+        assert_eq!(phys.to_string(), "1 x 2 (aspect ratio only)");
+    }
+
+    #[test]
+    fn test_last_modified() {
+        let data = [0x07, 0xe8, 2, 29, 13, 5, 9];
+        let time = LastModified::from_chunk(&chunk("tIME", &data)).unwrap();
+        assert_eq!(time.year, 2024);
+        assert_eq!(time.to_string(), "2024-02-29T13:05:09Z");
+    }
+
+    #[test]
+    fn test_last_modified_round_trip() {
+        let time = DateTime::parse_from_rfc3339("2024-02-29T13:05:09Z").unwrap().to_utc();
+        let modified = LastModified::from_datetime(time).unwrap();
+        let chunk = modified.to_chunk();
+        assert_eq!(chunk.data(), [0x07, 0xe8, 2, 29, 13, 5, 9]);
+        assert_eq!(LastModified::from_chunk(&chunk).unwrap().to_datetime(), Some(time));
+    }
+
+    #[test]
+    fn test_last_modified_invalid() {
+        let error = LastModified::from_chunk(&chunk("tIME", &[0x07, 0xe8, 13, 1, 0, 0, 0])).unwrap_err();
+        assert_eq!(error.to_string(), "tIME month 13 is out of range, expected 1 to 12");
+        assert!(LastModified::from_chunk(&chunk("tIME", &[0x07, 0xe8, 1, 1, 24, 0, 0])).is_err());
+        let error = LastModified::from_chunk(&chunk("tIME", &[0x07, 0xe8, 1])).unwrap_err();
+        assert_eq!(error.to_string(), "tIME chunk should hold 7 bytes, found 3");
+    }
+
+    fn iccp_chunk(compressed: &[u8]) -> Chunk {
+        let mut data = b"sRGB IEC61966-2.1\0\0".to_vec();
+        data.extend_from_slice(compressed);
+        chunk("iCCP", &data)
+    }
+
+    #[test]
+    fn test_icc_profile() {
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"not a real profile, just some bytes").unwrap();
+        let icc = IccProfile::from_chunk(&iccp_chunk(&encoder.finish().unwrap())).unwrap();
+
+        assert_eq!(icc.name, "sRGB IEC61966-2.1");
+        assert_eq!(icc.profile, b"not a real profile, just some bytes");
+    }
+
+    #[test]
+    fn test_icc_profile_invalid() {
+        let error = IccProfile::from_chunk(&iccp_chunk(b"garbage")).unwrap_err();
+        assert_eq!(error.to_string(), "iCCP profile data is not a valid zlib stream");
+        assert!(IccProfile::from_chunk(&chunk("iCCP", b"no terminator")).is_err());
+        assert!(IccProfile::from_chunk(&chunk("iCCP", b"name\0")).is_err());
+    }
+
+    #[test]
+    fn test_physical_dimensions_wrong_length() {
+        let error = PhysicalDimensions::from_chunk(&chunk("pHYs", &[0; 8])).unwrap_err();
+        assert_eq!(error.to_string(), "pHYs chunk should hold 9 bytes, found 8");
+        assert!(PhysicalDimensions::from_chunk(&chunk("gAMA", &[0; 9])).is_err());
+    }
+
+    #[test]
+    fn test_text_entry() {
+        let entry = TextEntry::from_chunk(&chunk("tEXt", b"Author\0Jos\xe9")).unwrap();
+        assert_eq!(entry, TextEntry::new("Author", "José").unwrap());
+        assert_eq!(entry.to_chunk().data(), b"Author\0Jos\xe9");
+        assert_eq!(TextEntry::from_chunk(&chunk("tEXt", b"Empty\0")).unwrap().text, "");
+
+        assert!(TextEntry::from_chunk(&chunk("tEXt", b"no separator")).is_err());
+        assert!(TextEntry::from_chunk(&chunk("tEXt", b"\0no keyword")).is_err());
+        assert!(TextEntry::from_chunk(&chunk("zTXt", b"Author\0x")).is_err());
+        assert!(TextEntry::new(&"k".repeat(80), "text").is_err());
+        assert!(TextEntry::new("Title", "snowman \u{2603}").is_err());
+    }
+}
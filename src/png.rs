@@ -1,22 +1,105 @@
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use crate::chunk::Chunk;
 
-use anyhow::{bail, Result, anyhow};
+use anyhow::{bail, Context, Result, anyhow};
+use log::debug;
 use thiserror::Error;
 use crate::chunk_type::ChunkType;
+use crate::progress;
+use crate::validation::ValidationReport;
 
-struct Png {
-    chunks: Vec<Chunk>
+// from_file refuses anything bigger, set once from --max-size
+static MAX_FILE_SIZE: AtomicU64 = AtomicU64::new(Png::DEFAULT_MAX_FILE_SIZE);
+
+// Safety: the map is only valid while nobody truncates or rewrites the file underneath it,
+// which memmap2 can't rule out. We only map for read-only commands, parse the bytes into owned
+// chunks straight away and drop the map before returning, so the window is the parse itself.
+// Writes from this tool go through a temp file and a rename, which leaves an existing map alone.
+#[cfg(feature = "mmap")]
+fn map_file(path: &Path) -> Option<memmap2::Mmap> {
+    let file = fs::File::open(path).ok()?;
+    unsafe { memmap2::Mmap::map(&file) }.ok()
+}
+
+fn read_with_progress(path: &Path, size: u64) -> io::Result<Vec<u8>> {
+    let bar = progress::bytes(size);
+    bar.set_message(format!("reading {}", path.display()));
+    let mut bytes = Vec::with_capacity(size as usize);
+    bar.wrap_read(fs::File::open(path)?).read_to_end(&mut bytes)?;
+    bar.finish_and_clear();
+    Ok(bytes)
+}
+
+pub struct Png {
+    chunks: Vec<Chunk>,
+    // whatever follows the IEND chunk, kept so rewriting a file doesn't silently drop it
+    trailing: Vec<u8>,
 }
 
 #[derive(Debug, Error)]
-enum PngError {
+pub enum PngError {
     #[error("Doesn't contain the standard header bytes for png files")]
     IncorrectPNGSignature,
-    #[error("The first chunk should be a IHDR chunk")]
-    InvalidFirstChunk
-    
+    #[error("Chunk not found: {0}")]
+    ChunkNotFound(String),
+    #[error("{size} bytes is larger than the {limit} byte limit, see --max-size")]
+    TooLarge { size: u64, limit: u64 },
+    // `offset` is where the unfinished field starts, `parsed` how many whole chunks came before
+    #[error("Truncated while reading chunk {part} at offset {offset}, after {parsed} complete chunks")]
+    Truncated { part: &'static str, offset: usize, parsed: usize },
+}
+
+// which field of the chunk starting at `offset` runs past the end of the input, if any
+fn truncated_part(value: &[u8], offset: usize) -> Option<(&'static str, usize)> {
+    let rest = &value[offset..];
+    let Some(length) = rest.get(0..4) else {
+        return Some(("length", offset));
+    };
+    let length = u32::from_be_bytes(length.try_into().unwrap());
+    // an impossible length is left to Chunk::try_from, which reports it as LengthTooLarge
+    if length > Chunk::MAX_LENGTH {
+        return None;
+    }
+    if rest.len() < 8 {
+        return Some(("type", offset + 4));
+    }
+    let length = length as usize;
+    if rest.len() < 8 + length {
+        return Some(("data", offset + 8));
+    }
+    if rest.len() < 12 + length {
+        return Some(("crc", offset + 8 + length));
+    }
+    None
+}
+
+// the inner reader gets an error instead of more bytes once the budget is used up
+struct Budget<R> {
+    inner: R,
+    remaining: u64,
+    limit: u64,
+}
+
+impl<R: Read> Read for Budget<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            // running out exactly at the end of the input is fine
+            return match self.inner.read(&mut [0])? {
+                0 => Ok(0),
+                _ => Err(io::Error::other(format!("Input exceeds the {} byte limit", self.limit))),
+            };
+        }
+        let wanted = buf.len().min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..wanted])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
 }
 
 // this wants a proper PNG file while the from_chunks below will contain an incomplete png
@@ -35,48 +118,59 @@ impl TryFrom<&[u8]> for Png {
             bail!(PngError::IncorrectPNGSignature);
         }
         
-        // now we need to examine the IHDR chunk
-        // length is roughly variable
-        let image_header_length = u32::from_be_bytes(value
-            .get(8..12)
-            .ok_or(anyhow::anyhow!("Failed to read the png header length"))?
-            .try_into()?);
-        let chunk_type_array: [u8; 4] = value.get(12..16)
-            .ok_or(anyhow!("Input slice is too short, not of size 8 for chunk type"))?
-            .try_into()?;
-        let chunk_type = ChunkType::try_from(chunk_type_array)?;
+        // IHDR goes through Chunk::try_from like every other chunk, so its stored CRC is
+        // checked and kept rather than recomputed
+        let mut next_chunk_offset = Self::STANDARD_HEADER.len();
+        if value.len() == next_chunk_offset {
+            bail!("Failed to read the png header length");
+        }
+        while next_chunk_offset < value.len() {
+            if let Some((part, offset)) = truncated_part(value, next_chunk_offset) {
+                bail!(PngError::Truncated { part, offset, parsed: chunks.len() });
+            }
+            let new_chunk = Chunk::try_from(&value[next_chunk_offset..])?;
 
-        // if chunk_type != ChunkType::from_str("IHDR").expect("IHDR should be valid chunktype") {
-        //     bail!(PngError::InvalidFirstChunk);
-        // }
+            debug!(
+                "Parsed {} chunk: length {}, offset {next_chunk_offset}",
+                new_chunk.chunk_type(),
+                new_chunk.length()
+            );
+            let is_end = new_chunk.chunk_type() == &ChunkType::IEND;
+            next_chunk_offset += 12 + new_chunk.length() as usize;
+            chunks.push(new_chunk);
+            if is_end {
+                break;
+            }
+        }
 
-        let ihdr_chunk_data = value.get(16..16+image_header_length as usize)
-            .ok_or(anyhow!("Failed to read the png image metadata"))?
-            .to_vec();
+        let trailing = value.get(next_chunk_offset..).unwrap_or_default().to_vec();
+        if !trailing.is_empty() {
+            debug!("Found {} bytes after IEND at offset {next_chunk_offset}", trailing.len());
+        }
+        Ok(Self { chunks, trailing })
+    }
+}
 
-        let ihdr_chunk = Chunk::new(chunk_type, ihdr_chunk_data);
-        chunks.push(ihdr_chunk);
+impl TryFrom<&Vec<u8>> for Png {
+    type Error = anyhow::Error;
 
-        // skip next 4 + image_header_length bytes
+    fn try_from(value: &Vec<u8>) -> Result<Self, Self::Error> {
+        Png::try_from(value.as_slice())
+    }
+}
 
-        // now parse the remainder chunks (which includes IEND)
+impl TryFrom<Vec<u8>> for Png {
+    type Error = anyhow::Error;
 
-        let mut next_chunk_offset: usize = (16 + image_header_length + 4) as usize;
-        while next_chunk_offset < value.len() {
-            // analyse length of chunk
-            let data_length = u32::from_be_bytes(value
-                .get(next_chunk_offset..next_chunk_offset + 4)
-                .ok_or(anyhow::anyhow!("Failed to read the chunk length"))?
-                .try_into()?);
-
-            let total_chunk_length = 8 + ChunkType::length() + data_length;
-            let new_chunk = Chunk::try_from(&value[next_chunk_offset..next_chunk_offset+total_chunk_length as 
-                usize])?;
-            chunks.push(new_chunk);
-            next_chunk_offset += total_chunk_length as usize;
-        }
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Png::try_from(value.as_slice())
+    }
+}
 
-        Ok(Self { chunks })
+// the serialized file, trailing bytes included, the same bytes as as_bytes
+impl From<&Png> for Vec<u8> {
+    fn from(png: &Png) -> Vec<u8> {
+        png.as_bytes()
     }
 }
 
@@ -86,7 +180,15 @@ impl Display for Png {
         writeln!(f, "Number of chunks: {}", self.chunks.len())?;
 
         for (index, chunk) in self.chunks.iter().enumerate() {
-            writeln!(f, "Chunk {}: {}", index + 1, chunk.chunk_type().to_string())?;
+            writeln!(f, "Chunk {}: {}", index + 1, chunk.chunk_type())?;
+        }
+        if !self.trailing.is_empty() {
+            writeln!(
+                f,
+                "Trailing data: {} bytes after IEND at offset {}",
+                self.trailing.len(),
+                self.trailing_offset()
+            )?;
         }
         Ok(())
     }
@@ -95,64 +197,532 @@ impl Display for Png {
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
     
-    fn from_chunks(chunks: Vec<Chunk>) -> Png {
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
         Png {
-            chunks
+            chunks,
+            trailing: Vec::new(),
         }
     }
+
+    pub const DEFAULT_MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+
+    pub fn set_max_file_size(limit: u64) {
+        MAX_FILE_SIZE.store(limit, Ordering::Relaxed);
+    }
+
+    pub fn max_file_size() -> u64 {
+        MAX_FILE_SIZE.load(Ordering::Relaxed)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Png> {
+        Png::from_file_with_limit(path, Png::max_file_size())
+    }
+
+    // the size comes from the metadata, so an oversized file is refused without reading it
+    pub fn from_file_with_limit(path: &Path, limit: u64) -> Result<Png> {
+        let size = fs::metadata(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .len();
+        if size > limit {
+            return Err(PngError::TooLarge { size, limit })
+                .with_context(|| format!("Refusing to load {}", path.display()));
+        }
+        let bytes = if size >= progress::LARGE_FILE {
+            read_with_progress(path, size)
+        } else {
+            fs::read(path)
+        };
+        let bytes = bytes.with_context(|| format!("Failed to read {}", path.display()))?;
+        Png::try_from(bytes.as_slice())
+    }
+
+    // for read-only commands, parses straight out of a memory map when the mmap feature is on
+    // and falls back to from_file when it's off or the file can't be mapped
+    pub fn from_file_mapped(path: &Path) -> Result<Png> {
+        #[cfg(feature = "mmap")]
+        {
+            let limit = Png::max_file_size();
+            if let Some(map) = map_file(path) {
+                let size = map.len() as u64;
+                if size > limit {
+                    return Err(PngError::TooLarge { size, limit })
+                        .with_context(|| format!("Refusing to load {}", path.display()));
+                }
+                return Png::try_from(&map[..]);
+            }
+            debug!("Couldn't map {}, reading it instead", path.display());
+        }
+        Png::from_file(path)
+    }
+
+    // the body is parsed as it streams in, so a download over the limit stops there
+    #[cfg(feature = "net")]
+    pub fn from_url(url: &str) -> Result<Png> {
+        Png::from_url_with_limit(url, Png::max_file_size())
+    }
+
+    #[cfg(feature = "net")]
+    pub fn from_url_with_limit(url: &str, limit: u64) -> Result<Png> {
+        let response = reqwest::blocking::get(url)
+            .with_context(|| format!("Failed to fetch {url}"))?;
+        let status = response.status();
+        if status != reqwest::StatusCode::OK {
+            bail!("Failed to fetch {url}: server responded with {status}");
+        }
+        if let Some(size) = response.content_length().filter(|&size| size > limit) {
+            return Err(PngError::TooLarge { size, limit })
+                .with_context(|| format!("Refusing to load {url}"));
+        }
+        Png::from_reader_with_limit(response, limit)
+            .with_context(|| format!("Failed to load {url}"))
+    }
+
+    // same size check and parser as from_file, only the reading is async
+    #[cfg(feature = "async")]
+    pub async fn from_file_async(path: &Path) -> Result<Png> {
+        let limit = Png::max_file_size();
+        let size = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .len();
+        if size > limit {
+            return Err(PngError::TooLarge { size, limit })
+                .with_context(|| format!("Refusing to load {}", path.display()));
+        }
+        let bytes = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Png::try_from(bytes.as_slice())
+    }
+
+    // for input whose size isn't known up front, reading stops with an error past `limit` bytes
+    pub fn from_reader_with_limit<R: Read>(reader: R, limit: u64) -> Result<Png> {
+        Png::from_reader(Budget { inner: reader, remaining: limit, limit })
+    }
     
-    fn append_chunk(&mut self, chunk: Chunk) {
+    // parses chunk by chunk as the bytes come in instead of needing the whole file in memory
+    pub fn from_reader<R: Read>(reader: R) -> Result<Png> {
+        Png::read(reader, true)
+    }
+
+    // for looking at damaged files, chunks whose CRC doesn't match are kept as they are
+    pub fn from_file_lenient(path: &Path) -> Result<Png> {
+        let limit = Png::max_file_size();
+        let file = fs::File::open(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Png::read(Budget { inner: file, remaining: limit, limit }, false)
+            .with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    fn read<R: Read>(reader: R, check_crc: bool) -> Result<Png> {
+        let mut reader = BufReader::new(reader);
+
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)
+            .context("Unexpected end of input while reading the png header bytes")?;
+        if header != Self::STANDARD_HEADER {
+            bail!(PngError::IncorrectPNGSignature);
+        }
+
+        let mut chunks = Vec::new();
+        while !reader.fill_buf()?.is_empty() {
+            let chunk = if check_crc {
+                Chunk::from_reader(&mut reader)?
+            } else {
+                Chunk::from_reader_unchecked(&mut reader)?
+            };
+            let is_end = chunk.chunk_type() == &ChunkType::IEND;
+            chunks.push(chunk);
+            if is_end {
+                break;
+            }
+        }
+
+        let mut trailing = Vec::new();
+        reader.read_to_end(&mut trailing)?;
+        Ok(Png { chunks, trailing })
+    }
+
+    pub fn append_chunk(&mut self, chunk: Chunk) {
         self.chunks.push(chunk);
     }
 
-    fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+    // keeps IEND as the last chunk, falls back to appending when there isn't one
+    pub fn insert_before_iend(&mut self, chunk: Chunk) {
+        match self.chunks.iter().rposition(|c| c.chunk_type() == &ChunkType::IEND) {
+            Some(index) => self.chunks.insert(index, chunk),
+            None => self.chunks.push(chunk),
+        }
+    }
+
+    // right after the first chunk of `after`, returning the index it landed at. Without one
+    // the chunk is handed back so the caller can decide where else it goes
+    pub fn insert_after_type(&mut self, after: &ChunkType, chunk: Chunk) -> Result<usize, Chunk> {
+        match self.chunks.iter().position(|c| c.chunk_type() == after) {
+            Some(index) => {
+                self.chunks.insert(index + 1, chunk);
+                Ok(index + 1)
+            }
+            None => Err(chunk),
+        }
+    }
+
+    // swaps `chunk` in for the first chunk `matches` accepts and drops any later ones, so
+    // there's only one afterwards. Without a match it goes before IEND. True when replaced
+    pub fn replace_or_insert(&mut self, chunk: Chunk, matches: impl Fn(&Chunk) -> bool) -> bool {
+        let Some(index) = self.chunks.iter().position(&matches) else {
+            self.insert_before_iend(chunk);
+            return false;
+        };
+        self.chunks[index] = chunk;
+        let mut position = 0;
+        self.chunks.retain(|chunk| {
+            position += 1;
+            position <= index + 1 || !matches(chunk)
+        });
+        true
+    }
+
+    // appends an empty IEND unless the file already has one, true when it was added
+    pub fn ensure_iend(&mut self) -> bool {
+        if self.chunks.iter().any(|chunk| chunk.chunk_type() == &ChunkType::IEND) {
+            return false;
+        }
+        self.chunks.push(Chunk::new(ChunkType::IEND, vec![]));
+        true
+    }
+
+    pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
         let to_find = ChunkType::from_str(chunk_type)
             .map_err(|e| anyhow!("Invalid chunk type: {e}"))?;
         
+        self.remove_first_of_type(&to_find)
+            .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()).into())
+    }
+
+    pub fn remove_first_of_type(&mut self, chunk_type: &ChunkType) -> Option<Chunk> {
         self.chunks
             .iter()
-            .position(|chunk| chunk.chunk_type() == &to_find)
+            .position(|chunk| chunk.chunk_type() == chunk_type)
             .map(|index| self.chunks.remove(index))
-            .ok_or_else(|| anyhow!("Chunk not found: {chunk_type}"))
     }
     
-    fn header(&self) -> &[u8; 8] {
+    // removes every ancillary chunk and returns them, IEND is always kept
+    pub fn strip_ancillary(&mut self) -> Vec<Chunk> {
+        let (kept, removed) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|chunk| {
+                chunk.chunk_type().is_critical() || chunk.chunk_type() == &ChunkType::IEND
+            });
+        self.chunks = kept;
+        removed
+    }
+
+    // drops every chunk equal to an earlier one and returns them, the first occurrence stays.
+    // IDAT is left alone unless asked for, its chunks legitimately repeat
+    pub fn dedup(&mut self, include_idat: bool) -> Vec<Chunk> {
+        let mut seen = HashSet::new();
+        let mut kept: Vec<Chunk> = Vec::with_capacity(self.chunks.len());
+        let mut removed = Vec::new();
+        for chunk in std::mem::take(&mut self.chunks) {
+            let skip = !include_idat && chunk.chunk_type() == &ChunkType::IDAT;
+            if !skip && !seen.insert((chunk.chunk_type().clone(), chunk.data().to_vec())) {
+                removed.push(chunk);
+            } else {
+                kept.push(chunk);
+            }
+        }
+        self.chunks = kept;
+        removed
+    }
+
+    // takes the first chunk of the type out and puts it back at `to_index`, counted after
+    // the removal, so the chunk ends up at exactly that index. Returns where it was
+    pub fn move_chunk(&mut self, chunk_type: &ChunkType, to_index: usize) -> Result<usize> {
+        let from = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type() == chunk_type)
+            .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+        if to_index >= self.chunks.len() {
+            bail!(
+                "Index {to_index} is past the last chunk, there are {} chunks",
+                self.chunks.len()
+            );
+        }
+        let chunk = self.chunks.remove(from);
+        self.chunks.insert(to_index, chunk);
+        Ok(from)
+    }
+
+    // the type feeds the CRC, which set_chunk_type recomputes
+    pub fn rename_type(&mut self, from: &ChunkType, to: &ChunkType) -> usize {
+        let mut renamed = 0;
+        for chunk in self.chunks.iter_mut().filter(|chunk| chunk.chunk_type() == from) {
+            chunk.set_chunk_type(to.clone());
+            renamed += 1;
+        }
+        renamed
+    }
+
+    pub fn header(&self) -> &[u8; 8] {
         &Self::STANDARD_HEADER
     }
     
-    fn chunks(&self) -> &[Chunk] {
+    pub fn chunks(&self) -> &[Chunk] {
         self.chunks.as_slice()
     }
+
+    // bytes after the IEND chunk's CRC, empty for a well-formed file
+    pub fn trailing_bytes(&self) -> &[u8] {
+        self.trailing.as_slice()
+    }
+
+    pub fn strip_trailing(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.trailing)
+    }
+
+    // where each chunk's length field starts in the serialized file, computed rather than
+    // stored so edits never leave it stale
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        self.chunks
+            .iter()
+            .scan(Self::STANDARD_HEADER.len(), |offset, chunk| {
+                let start = *offset;
+                *offset += 12 + chunk.data().len();
+                Some(start)
+            })
+            .collect()
+    }
+
+    // where the trailing bytes start in the serialized file
+    pub fn trailing_offset(&self) -> usize {
+        let chunks_length: usize = self.chunks.iter().map(|chunk| 12 + chunk.data().len()).sum();
+        Self::STANDARD_HEADER.len() + chunks_length
+    }
+
+    // the length as_bytes would return, trailing bytes included, without building the buffer
+    pub fn total_size(&self) -> usize {
+        self.trailing_offset() + self.trailing.len()
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
     
     
-    // requires that chunk_type is a 4 character str
-    fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
-        ChunkType::from_str(chunk_type)
-            .ok()
-            .and_then(|to_find| {
-                self.chunks.iter().find(|&chunk| chunk.chunk_type() == &to_find)
-            })
+    // a string that isn't a 4 character chunk type simply matches nothing
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.chunks.iter().find(|chunk| chunk.is_type(chunk_type))
     }
     
-    fn as_bytes(&self) -> Vec<u8> {
-        let mut result = Vec::new();
+    // chunk types the spec allows at most once that appear more often, with their counts
+    pub fn duplicate_unique_types(&self) -> Vec<(&ChunkType, usize)> {
+        let mut counts: Vec<(&ChunkType, usize)> = Vec::new();
+        for chunk in self.chunks.iter().filter(|chunk| !chunk.chunk_type().allows_multiple()) {
+            match counts.iter_mut().find(|(chunk_type, _)| *chunk_type == chunk.chunk_type()) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((chunk.chunk_type(), 1)),
+            }
+        }
+        counts.retain(|&(_, count)| count > 1);
+        counts
+    }
+
+    // collects every structural problem without printing anything, see ValidationReport
+    pub fn validate(&self) -> ValidationReport {
+        ValidationReport::of(self)
+    }
+
+    pub fn chunks_of_type<'a>(&'a self, chunk_type: &'a ChunkType) -> impl Iterator<Item = &'a Chunk> {
+        self.chunks.iter().filter(move |chunk| chunk.chunk_type() == chunk_type)
+    }
+
+    /// Iterates over the chunks in file order. The filters from [`ChunkFilter`] can be
+    /// chained onto it, none of them allocate.
+    ///
+    /// ```
+    /// use std::str::FromStr;
+    /// use pngme::chunk::Chunk;
+    /// use pngme::chunk_type::ChunkType;
+    /// use pngme::png::{ChunkFilter, Png};
+    ///
+    /// let chunk = |chunk_type: &str, data: &str| {
+    ///     Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    /// };
+    /// let png = Png::from_chunks(vec![
+    ///     chunk("IHDR", "header"),
+    ///     chunk("tEXt", "Title\0Dice"),
+    ///     chunk("IDAT", "pixels"),
+    ///     chunk("tEXt", "Author\0Someone"),
+    ///     chunk("IEND", ""),
+    /// ]);
+    ///
+    /// assert_eq!(png.chunks_iter().critical().count(), 3);
+    /// assert_eq!(png.chunks_iter().ancillary().count(), 2);
+    ///
+    /// let texts: Vec<&[u8]> = png.chunks_iter().of_type("tEXt").map(Chunk::data).collect();
+    /// assert_eq!(texts, [&b"Title\0Dice"[..], &b"Author\0Someone"[..]]);
+    /// ```
+    pub fn chunks_iter(&self) -> ChunksIter<'_> {
+        ChunksIter { inner: self.chunks.iter() }
+    }
 
-        result.extend_from_slice(self.header());
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        self.write_to(&mut result).expect("writing to a Vec can't fail");
+        result
+    }
 
+    // streams the header and every chunk without building the whole file in memory first
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(self.header())?;
         for chunk in self.chunks() {
-            result.extend(chunk.as_bytes())
+            chunk.write_to(w)?;
         }
+        w.write_all(self.trailing_bytes())
+    }
 
-        result
+    // serialized up front with as_bytes, then written and flushed in one go
+    #[cfg(feature = "async")]
+    pub async fn write_to_async<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::AsyncWriteExt;
 
+        w.write_all(&self.as_bytes()).await?;
+        w.flush().await
+    }
+}
+
+/// The iterator returned by [`Png::chunks_iter`].
+pub struct ChunksIter<'a> {
+    inner: std::slice::Iter<'a, Chunk>,
+}
+
+impl<'a> Iterator for ChunksIter<'a> {
+    type Item = &'a Chunk;
+
+    fn next(&mut self) -> Option<&'a Chunk> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for ChunksIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl ExactSizeIterator for ChunksIter<'_> {}
+
+/// Filters for any iterator over chunks, so they can be combined with each other and
+/// with the usual iterator adaptors.
+///
+/// ```
+/// use std::str::FromStr;
+/// use pngme::chunk::Chunk;
+/// use pngme::chunk_type::ChunkType;
+/// use pngme::png::{ChunkFilter, Png};
+///
+/// let chunk = |chunk_type: &str| Chunk::new(ChunkType::from_str(chunk_type).unwrap(), Vec::new());
+/// let png = Png::from_chunks(vec![chunk("IHDR"), chunk("IDAT"), chunk("IDAT"), chunk("IEND")]);
+///
+/// // the last IDAT, found by filtering from the back
+/// let last_idat = png.chunks_iter().critical().of_type("IDAT").next_back();
+/// assert!(std::ptr::eq(last_idat.unwrap(), &png.chunks()[2]));
+/// assert_eq!(png.chunks_iter().ancillary().of_type("IDAT").count(), 0);
+/// ```
+pub trait ChunkFilter<'a>: Iterator<Item = &'a Chunk> + Sized {
+    /// Only chunks a decoder must understand, uppercase first letter.
+    fn critical(self) -> Critical<Self> {
+        Critical { inner: self }
+    }
+
+    /// Only chunks a decoder may skip, lowercase first letter.
+    fn ancillary(self) -> Ancillary<Self> {
+        Ancillary { inner: self }
+    }
+
+    /// Only chunks of the given type. A string that isn't 4 bytes long matches nothing.
+    fn of_type(self, chunk_type: &str) -> OfType<Self> {
+        OfType { inner: self, chunk_type: chunk_type.as_bytes().try_into().ok() }
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a Chunk>> ChunkFilter<'a> for I {}
+
+/// The iterator returned by [`ChunkFilter::critical`].
+pub struct Critical<I> {
+    inner: I,
+}
+
+/// The iterator returned by [`ChunkFilter::ancillary`].
+pub struct Ancillary<I> {
+    inner: I,
+}
+
+/// The iterator returned by [`ChunkFilter::of_type`].
+pub struct OfType<I> {
+    inner: I,
+    chunk_type: Option<[u8; 4]>,
+}
+
+impl<'a, I: Iterator<Item = &'a Chunk>> Iterator for Critical<I> {
+    type Item = &'a Chunk;
+
+    fn next(&mut self) -> Option<&'a Chunk> {
+        self.inner.find(|chunk| chunk.chunk_type().is_critical())
+    }
+}
+
+impl<'a, I: DoubleEndedIterator<Item = &'a Chunk>> DoubleEndedIterator for Critical<I> {
+    fn next_back(&mut self) -> Option<&'a Chunk> {
+        self.inner.rfind(|chunk| chunk.chunk_type().is_critical())
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a Chunk>> Iterator for Ancillary<I> {
+    type Item = &'a Chunk;
+
+    fn next(&mut self) -> Option<&'a Chunk> {
+        self.inner.find(|chunk| chunk.chunk_type().is_ancillary())
+    }
+}
+
+impl<'a, I: DoubleEndedIterator<Item = &'a Chunk>> DoubleEndedIterator for Ancillary<I> {
+    fn next_back(&mut self) -> Option<&'a Chunk> {
+        self.inner.rfind(|chunk| chunk.chunk_type().is_ancillary())
+    }
+}
+
+impl<'a, I: Iterator<Item = &'a Chunk>> Iterator for OfType<I> {
+    type Item = &'a Chunk;
+
+    fn next(&mut self) -> Option<&'a Chunk> {
+        let chunk_type = self.chunk_type?;
+        self.inner.find(|chunk| chunk.chunk_type().bytes() == chunk_type)
+    }
+}
+
+impl<'a, I: DoubleEndedIterator<Item = &'a Chunk>> DoubleEndedIterator for OfType<I> {
+    fn next_back(&mut self) -> Option<&'a Chunk> {
+        let chunk_type = self.chunk_type?;
+        self.inner.rfind(|chunk| chunk.chunk_type().bytes() == chunk_type)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk::Chunk;
+    use crate::chunk::{strategies, Chunk, ChunkError};
     use crate::chunk_type::ChunkType;
+    use proptest::prelude::*;
     use std::convert::TryFrom;
 
     fn testing_chunks() -> Vec<Chunk> {
@@ -198,7 +768,7 @@ mod tests {
             .copied()
             .collect();
 
-        let png = Png::try_from(bytes.as_ref());
+        let png = Png::try_from(&bytes);
 
         assert!(png.is_ok());
     }
@@ -216,7 +786,7 @@ mod tests {
             .copied()
             .collect();
 
-        let png = Png::try_from(bytes.as_ref());
+        let png = Png::try_from(&bytes);
 
         assert!(png.is_err());
     }
@@ -238,11 +808,96 @@ mod tests {
 
         chunk_bytes.append(&mut bad_chunk);
 
-        let png = Png::try_from(chunk_bytes.as_ref());
+        let png = Png::try_from(&chunk_bytes);
 
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_chunk_offsets() {
+        // laid out by hand: the 8 byte signature, then 12 bytes of framing around each payload
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend(raw_chunk(b"IHDR", &[0; 13]));
+        bytes.extend(raw_chunk(b"ruSt", b"hello"));
+        bytes.extend(raw_chunk(b"IDAT", b""));
+        bytes.extend(raw_chunk(b"IEND", b""));
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(png.chunk_offsets(), [8, 33, 50, 62]);
+        assert_eq!(png.trailing_offset(), bytes.len());
+        for (chunk, offset) in png.chunks().iter().zip(png.chunk_offsets()) {
+            assert_eq!(&bytes[offset + 4..offset + 8], &chunk.chunk_type().bytes());
+        }
+        assert!(Png::from_chunks(Vec::new()).chunk_offsets().is_empty());
+    }
+
+    #[test]
+    fn test_total_size_and_chunk_count() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(ChunkType::IEND, Vec::new()));
+        assert_eq!(png.total_size(), png.as_bytes().len());
+        assert_eq!(png.chunk_count(), png.chunks().len());
+
+        let mut bytes = png.as_bytes();
+        bytes.extend_from_slice(b"trailing");
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(png.total_size(), bytes.len());
+        assert_eq!(Png::from_chunks(Vec::new()).total_size(), 8);
+    }
+
+    #[test]
+    fn test_truncation_diagnostic() {
+        // FrSt spans 8..40 with its data at 16 and CRC at 36, miDl starts at 40
+        let bytes = testing_png().as_bytes();
+        let cases = [
+            (10, "length", 8, 0),
+            (14, "type", 12, 0),
+            (20, "data", 16, 0),
+            (38, "crc", 36, 0),
+            (42, "length", 40, 1),
+            (60, "data", 48, 1),
+        ];
+        for (end, part, offset, parsed) in cases {
+            let error = Png::try_from(&bytes[..end]).err().unwrap();
+            match error.downcast_ref::<PngError>() {
+                Some(PngError::Truncated { part: p, offset: o, parsed: n }) => {
+                    assert_eq!((*p, *o, *n), (part, offset, parsed), "cut at {end}")
+                }
+                _ => panic!("cut at {end}: {error}"),
+            }
+            assert!(error.to_string().contains(&format!("chunk {part} at offset {offset}")));
+        }
+
+        // a corrupt length past the limit is reported as such, not as missing data
+        let mut bytes = Png::STANDARD_HEADER.to_vec();
+        bytes.extend_from_slice(&[0xff, 0xff, 0xff, 0xff]);
+        bytes.extend_from_slice(b"ruStdata");
+        let error = Png::try_from(bytes.as_slice()).err().unwrap();
+        assert!(matches!(error.downcast_ref(), Some(ChunkError::LengthTooLarge(_))), "{error}");
+        let error = Png::try_from(&bytes[..14]).err().unwrap();
+        assert!(matches!(error.downcast_ref(), Some(ChunkError::LengthTooLarge(_))), "{error}");
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let from_bytes = Png::try_from(&PNG_FILE[..]).unwrap();
+        let from_reader = Png::from_reader(std::io::Cursor::new(&PNG_FILE[..])).unwrap();
+
+        assert_eq!(from_reader.chunks(), from_bytes.chunks());
+    }
+
+    #[test]
+    fn test_from_reader_errors() {
+        let error = Png::from_reader(&PNG_FILE[..5]).err().unwrap();
+        assert_eq!(error.to_string(), "Unexpected end of input while reading the png header bytes");
+
+        let error = Png::from_reader(&PNG_FILE[..30]).err().unwrap();
+        assert_eq!(error.to_string(), "Unexpected end of input while reading chunk crc");
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[0] = 13;
+        assert!(Png::from_reader(bytes.as_slice()).is_err());
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -267,6 +922,27 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_insert_before_iend() {
+        let mut png = testing_png();
+        png.insert_before_iend(chunk_from_strings("TeSt", "Message").unwrap());
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "TeSt");
+
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+        png.insert_before_iend(chunk_from_strings("TwOo", "Message").unwrap());
+        assert_eq!(&png.chunks()[4].chunk_type().to_string(), "TwOo");
+        assert_eq!(&png.chunks()[5].chunk_type().to_string(), "IEND");
+    }
+
+    #[test]
+    fn test_ensure_iend() {
+        let mut png = testing_png();
+        assert!(png.ensure_iend());
+        assert!(!png.ensure_iend());
+        assert_eq!(png.chunks().len(), testing_chunks().len() + 1);
+        assert_eq!(png.chunks().last(), Some(&Chunk::new(ChunkType::IEND, vec![])));
+    }
+
     #[test]
     fn test_remove_first_chunk() {
         let mut png = testing_png();
@@ -276,6 +952,306 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_duplicate_unique_types() {
+        let mut png = testing_png();
+        assert!(png.duplicate_unique_types().is_empty());
+
+        png.append_chunk(chunk_from_strings("IHDR", "first header").unwrap());
+        png.append_chunk(chunk_from_strings("IDAT", "some data").unwrap());
+        png.append_chunk(chunk_from_strings("IHDR", "second header").unwrap());
+        png.append_chunk(chunk_from_strings("IDAT", "more data").unwrap());
+
+        let duplicates = png.duplicate_unique_types();
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(&duplicates[0].0.to_string(), "IHDR");
+        assert_eq!(duplicates[0].1, 2);
+    }
+
+    #[test]
+    fn test_strip_ancillary() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("gAMA", "gamma").unwrap(),
+            chunk_from_strings("tEXt", "Author\0me").unwrap(),
+            chunk_from_strings("IDAT", "data").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        let removed = png.strip_ancillary();
+
+        let removed_types: Vec<String> = removed.iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(removed_types, vec!["gAMA", "tEXt"]);
+        assert!(png.chunks().iter().all(|chunk| chunk.chunk_type().is_critical()));
+        assert_eq!(png.chunks().len(), 3);
+        assert!(png.chunk_by_type("IEND").is_some());
+    }
+
+    #[test]
+    fn test_dedup() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("ruSt", "message").unwrap(),
+            chunk_from_strings("IDAT", "data").unwrap(),
+            chunk_from_strings("IDAT", "data").unwrap(),
+            chunk_from_strings("ruSt", "message").unwrap(),
+            chunk_from_strings("ruSt", "other").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        let removed = png.dedup(false);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].data(), b"message");
+        let types: Vec<String> = png.chunks().iter().map(Chunk::chunk_type_str).collect();
+        assert_eq!(types, vec!["IHDR", "ruSt", "IDAT", "IDAT", "ruSt", "IEND"]);
+
+        assert_eq!(png.dedup(true).len(), 1);
+        assert_eq!(png.chunks().len(), 5);
+        assert!(png.dedup(true).is_empty());
+    }
+
+    // raw chunk bytes with the CRC computed here, independent of Chunk::new
+    fn raw_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        digest.update(chunk_type);
+        digest.update(data);
+
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(chunk_type);
+        bytes.extend_from_slice(data);
+        bytes.extend(digest.finalize().to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip_keeps_unknown_chunks() {
+        let mut file = Png::STANDARD_HEADER.to_vec();
+        let chunks: [(&[u8; 4], &[u8]); 10] = [
+            (b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0]),
+            (b"sRGB", &[0]),
+            (b"prOp", &[0xff, 0x00, 0xfe, 0x01, 0x80]),
+            (b"zzZz", b""),
+            (b"RuSt", b"proprietary but critical"),
+            (b"IDAT", &[0x78, 0x9c, 0x63]),
+            (b"vpAg", &[0; 9]),
+            (b"IDAT", &[0x00, 0x00, 0x00, 0xff]),
+            (b"tEXt", b"Comment\0made by hand"),
+            (b"IEND", b""),
+        ];
+        for (chunk_type, data) in chunks {
+            file.extend(raw_chunk(chunk_type, data));
+        }
+        file.extend_from_slice(b"trailing junk");
+
+        let png = Png::try_from(file.as_slice()).unwrap();
+        assert_eq!(png.chunks().len(), chunks.len());
+        for (chunk, (chunk_type, data)) in png.chunks().iter().zip(chunks) {
+            assert_eq!(&chunk.chunk_type().bytes(), chunk_type);
+            assert_eq!(chunk.data(), data);
+        }
+        assert_eq!(png.as_bytes(), file);
+        assert_eq!(Png::from_reader(file.as_slice()).unwrap().as_bytes(), file);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("custom.png");
+        fs::write(&path, &file).unwrap();
+        let mut out = Vec::new();
+        Png::from_file(&path).unwrap().write_to(&mut out).unwrap();
+        assert_eq!(out, file);
+
+        // the IHDR CRC is checked like any other
+        file[8 + 8 + 13] ^= 1;
+        let error = Png::try_from(file.as_slice()).err().unwrap();
+        assert!(matches!(error.downcast_ref(), Some(ChunkError::InvalidCrc { .. })));
+    }
+
+    #[test]
+    fn test_chunks_iter_filters() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        // IHDR, IDAT, RuSt and IEND against sRGB, gAMA and pHYs
+        assert_eq!(png.chunks_iter().len(), 7);
+        assert_eq!(png.chunks_iter().critical().count(), 4);
+        assert_eq!(png.chunks_iter().ancillary().count(), 3);
+        assert_eq!(png.chunks_iter().of_type("IDAT").count(), 1);
+        assert_eq!(png.chunks_iter().ancillary().of_type("pHYs").count(), 1);
+        assert_eq!(png.chunks_iter().critical().of_type("pHYs").count(), 0);
+        assert_eq!(png.chunks_iter().of_type("nope").count(), 0);
+        assert_eq!(png.chunks_iter().of_type("toolong").count(), 0);
+
+        let critical: Vec<String> =
+            png.chunks_iter().critical().rev().map(Chunk::chunk_type_str).collect();
+        assert_eq!(critical, ["IEND", "RuSt", "IDAT", "IHDR"]);
+    }
+
+    #[test]
+    fn test_mapped_matches_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        fs::write(&path, PNG_FILE).unwrap();
+
+        let mapped = Png::from_file_mapped(&path).unwrap();
+        let read = Png::from_file(&path).unwrap();
+        assert_eq!(mapped.chunks().len(), read.chunks().len());
+        for (a, b) in mapped.chunks().iter().zip(read.chunks()) {
+            assert_eq!(a.as_bytes(), b.as_bytes());
+        }
+        assert_eq!(mapped.as_bytes(), read.as_bytes());
+    }
+
+    // answers a single request with `status` and `body`, returns the URL to fetch
+    #[cfg(feature = "net")]
+    fn serve_once(status: &'static str, body: Vec<u8>) -> String {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/test.png", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let head = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        });
+        url
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_from_url() {
+        let url = serve_once("200 OK", PNG_FILE.to_vec());
+        let png = Png::from_url(&url).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE);
+        assert!(png.chunk_by_type("RuSt").is_some());
+
+        let url = serve_once("404 Not Found", b"missing".to_vec());
+        let error = Png::from_url(&url).err().unwrap();
+        assert!(format!("{error:#}").contains("404"));
+
+        let url = serve_once("200 OK", PNG_FILE.to_vec());
+        let error = Png::from_url_with_limit(&url, 16).err().unwrap();
+        assert!(matches!(error.downcast_ref(), Some(PngError::TooLarge { .. })));
+    }
+
+    #[test]
+    fn test_file_over_limit_is_not_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("huge.png");
+        // sparse, so this doesn't actually take 4 GiB of disk, but reading it would take
+        // 4 GiB of memory
+        let file = fs::File::create(&path).unwrap();
+        file.set_len(4 * 1024 * 1024 * 1024).unwrap();
+
+        let error = Png::from_file_with_limit(&path, Png::DEFAULT_MAX_FILE_SIZE).err().unwrap();
+        assert!(matches!(error.downcast_ref(), Some(PngError::TooLarge { .. })));
+
+        fs::write(&path, PNG_FILE).unwrap();
+        assert!(Png::from_file_with_limit(&path, PNG_FILE.len() as u64 - 1).is_err());
+        assert!(Png::from_file_with_limit(&path, PNG_FILE.len() as u64).is_ok());
+    }
+
+    #[test]
+    fn test_reader_budget() {
+        assert!(Png::from_reader_with_limit(&PNG_FILE[..], PNG_FILE.len() as u64).is_ok());
+
+        // a chunk claiming the largest length followed by endless zeroes
+        let mut start = Png::STANDARD_HEADER.to_vec();
+        start.extend_from_slice(&Chunk::MAX_LENGTH.to_be_bytes());
+        start.extend_from_slice(b"ruSt");
+        let endless = std::io::Read::chain(start.as_slice(), std::io::repeat(0));
+        let error = Png::from_reader_with_limit(endless, 1024 * 1024).err().unwrap();
+        assert_eq!(error.to_string(), "Input exceeds the 1048576 byte limit");
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_async_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("async.png");
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        let mut file = tokio::fs::File::create(&path).await.unwrap();
+        png.write_to_async(&mut file).await.unwrap();
+        drop(file);
+
+        let read_back = Png::from_file_async(&path).await.unwrap();
+        assert_eq!(read_back.as_bytes(), PNG_FILE);
+    }
+
+    // collects every record logged while the tests run, they all share the one global logger
+    struct CapturingLogger(std::sync::Mutex<Vec<String>>);
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            let line = format!("{} {}", record.level(), record.args());
+            self.0.lock().unwrap().push(line);
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger(std::sync::Mutex::new(Vec::new()));
+
+    #[test]
+    fn test_parse_logs_each_chunk() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("lgTs", "logged").unwrap(),
+        ]);
+        Png::try_from(png.as_bytes()).unwrap();
+
+        let lines = LOGGER.0.lock().unwrap();
+        assert!(lines.contains(&"DEBUG Parsed IHDR chunk: length 6, offset 8".to_string()));
+        assert!(lines.contains(&"DEBUG Parsed lgTs chunk: length 6, offset 26".to_string()));
+    }
+
+    #[test]
+    fn test_rename_type() {
+        let mut png = testing_png();
+        let from = ChunkType::from_str("miDl").unwrap();
+        let to = ChunkType::from_str("neWt").unwrap();
+        let old_crc = png.chunks()[1].crc();
+
+        assert_eq!(png.rename_type(&from, &to), 1);
+
+        let chunk = png.chunk_by_type("neWt").unwrap();
+        assert_eq!(&chunk.data_as_string().unwrap(), "I am another chunk");
+        assert_ne!(chunk.crc(), old_crc);
+        assert!(png.chunk_by_type("miDl").is_none());
+        assert_eq!(png.rename_type(&from, &to), 0);
+    }
+
+    #[test]
+    fn test_move_chunk() {
+        let mut png = testing_png();
+        let types = |png: &Png| -> Vec<String> {
+            png.chunks().iter().map(Chunk::chunk_type_str).collect()
+        };
+        assert_eq!(png.move_chunk(&ChunkType::from_str("LASt").unwrap(), 0).unwrap(), 2);
+        assert_eq!(types(&png), ["LASt", "FrSt", "miDl"]);
+        assert_eq!(png.move_chunk(&ChunkType::from_str("LASt").unwrap(), 2).unwrap(), 0);
+        assert_eq!(types(&png), ["FrSt", "miDl", "LASt"]);
+        png.move_chunk(&ChunkType::from_str("FrSt").unwrap(), 0).unwrap();
+        assert_eq!(types(&png), ["FrSt", "miDl", "LASt"]);
+
+        assert!(png.move_chunk(&ChunkType::from_str("FrSt").unwrap(), 3).is_err());
+        assert!(png.move_chunk(&ChunkType::from_str("noPe").unwrap(), 0).is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -290,6 +1266,41 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_write_to() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut written = Vec::new();
+        png.write_to(&mut written).unwrap();
+        assert_eq!(written, png.as_bytes());
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.trailing_bytes().is_empty());
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"hidden payload");
+
+        let png = Png::try_from(&bytes).unwrap();
+        assert_eq!(png.trailing_bytes(), b"hidden payload");
+        assert_eq!(png.trailing_offset(), PNG_FILE.len());
+        assert_eq!(png.as_bytes(), bytes);
+        assert!(png.to_string().contains("Trailing data: 14 bytes after IEND at offset 4803"));
+
+        let png = Png::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(png.trailing_bytes(), b"hidden payload");
+    }
+
+    #[test]
+    fn test_png_try_from_vec() {
+        let bytes = PNG_FILE.to_vec();
+        assert_eq!(Png::try_from(&bytes).unwrap().as_bytes(), bytes);
+        let png = Png::try_from(bytes.clone()).unwrap();
+        assert_eq!(png.as_bytes(), bytes);
+        assert!(Png::try_from(b"not a png".to_vec()).is_err());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -303,9 +1314,13 @@ mod tests {
             .copied()
             .collect();
 
-        let png: Png = TryFrom::try_from(bytes.as_ref()).unwrap();
+        let png: Png = TryFrom::try_from(&bytes).unwrap();
 
         let _png_string = format!("{}", png);
+
+        let round_trip: Vec<u8> = (&png).into();
+        assert_eq!(round_trip, bytes);
+        assert_eq!(Vec::from(&png), png.as_bytes());
     }
 
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
@@ -554,4 +1569,30 @@ mod tests {
         202, 28, 31, 66, 176, 235, 16, 0, 0, 0, 3, 82, 117, 83, 116, 104, 101, 121, 158, 176, 245,
         160, 0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
     ];
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(1024))]
+
+        // an IEND in the middle would end parsing early, so only the last chunk may be one
+        #[test]
+        fn test_png_round_trips(
+            chunks in prop::collection::vec(
+                strategies::chunk().prop_filter("IEND ends the file", |chunk| {
+                    chunk.chunk_type() != &ChunkType::IEND
+                }),
+                0..8,
+            ),
+            trailing in prop::collection::vec(any::<u8>(), 0..16),
+        ) {
+            let mut chunks = chunks;
+            chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+            let mut bytes = Png::from_chunks(chunks.clone()).as_bytes();
+            bytes.extend_from_slice(&trailing);
+
+            let png = Png::try_from(bytes.as_slice()).unwrap();
+            prop_assert_eq!(png.chunks(), chunks.as_slice());
+            prop_assert_eq!(png.trailing_bytes(), trailing.as_slice());
+            prop_assert_eq!(png.as_bytes(), bytes);
+        }
+    }
 }
@@ -0,0 +1,42 @@
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+// turned off by --quiet
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+// single files below this parse and write too quickly for a bar to be worth drawing
+pub const LARGE_FILE: u64 = 64 * 1024 * 1024;
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+// drawn on stderr so piped stdout stays clean, and hidden when stderr isn't a terminal. A
+// hidden bar still counts, so callers don't need to care which one they got
+pub fn files(total: usize) -> ProgressBar {
+    let bar = ProgressBar::with_draw_target(Some(total as u64), draw_target());
+    bar.set_style(
+        ProgressStyle::with_template("{bar:40} {pos}/{len} files {wide_msg}")
+            .expect("progress template should be valid"),
+    );
+    bar
+}
+
+pub fn bytes(total: u64) -> ProgressBar {
+    let bar = ProgressBar::with_draw_target(Some(total), draw_target());
+    bar.set_style(
+        ProgressStyle::with_template("{bar:40} {bytes}/{total_bytes} {wide_msg}")
+            .expect("progress template should be valid"),
+    );
+    bar
+}
+
+fn draw_target() -> ProgressDrawTarget {
+    if ENABLED.load(Ordering::Relaxed) && io::stderr().is_terminal() {
+        ProgressDrawTarget::stderr()
+    } else {
+        ProgressDrawTarget::hidden()
+    }
+}
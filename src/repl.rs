@@ -0,0 +1,275 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+
+use crate::args::{DecodeFormat, WriteArgs};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::commands::{check_encode_type, render_message, write_png};
+use crate::envelope;
+use crate::png::{Png, PngError};
+
+const HELP: &str = "\
+list                    list the chunks
+decode <TYPE>           print the message in the first chunk of TYPE
+remove <TYPE>           remove the first chunk of TYPE
+encode <TYPE> <MESSAGE> add a chunk holding MESSAGE, quote it if it has spaces
+save                    write the changes back to the file
+quit                    leave, quit! leaves without saving";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReplCommand {
+    List,
+    Decode(ChunkType),
+    Remove(ChunkType),
+    Encode(ChunkType, String),
+    Save,
+    Quit { discard: bool },
+    Help,
+}
+
+// blank lines parse to None so the loop just prompts again
+pub fn parse_line(line: &str) -> Result<Option<ReplCommand>> {
+    let words = split_words(line)?;
+    let Some((name, args)) = words.split_first() else {
+        return Ok(None);
+    };
+    let command = match (name.as_str(), args) {
+        ("list" | "ls", []) => ReplCommand::List,
+        ("decode", [chunk_type]) => ReplCommand::Decode(ChunkType::from_str(chunk_type)?),
+        ("remove" | "rm", [chunk_type]) => ReplCommand::Remove(ChunkType::from_str(chunk_type)?),
+        ("encode", [chunk_type, message]) => {
+            ReplCommand::Encode(ChunkType::from_str(chunk_type)?, message.clone())
+        }
+        ("save", []) => ReplCommand::Save,
+        ("quit" | "exit", []) => ReplCommand::Quit { discard: false },
+        ("quit!" | "exit!", []) => ReplCommand::Quit { discard: true },
+        ("help" | "?", []) => ReplCommand::Help,
+        ("list" | "ls" | "decode" | "remove" | "rm" | "encode" | "save" | "quit" | "exit"
+        | "quit!" | "exit!" | "help" | "?", _) => {
+            bail!("Wrong number of arguments for {name}, try help")
+        }
+        _ => bail!("Unknown command {name}, try help"),
+    };
+    Ok(Some(command))
+}
+
+// whitespace separated, double quotes group words and a backslash escapes the next character
+fn split_words(line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                None => bail!("Trailing backslash"),
+            },
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        bail!("Unterminated quote");
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+// the file is parsed once, every command works on the copy in memory until save
+pub struct Repl {
+    path: PathBuf,
+    png: Png,
+    unsaved: bool,
+}
+
+impl Repl {
+    pub fn new(path: &Path, png: Png) -> Repl {
+        Repl { path: path.to_path_buf(), png, unsaved: false }
+    }
+
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.unsaved
+    }
+
+    // returns false once the loop should stop
+    pub fn execute<W: Write>(
+        &mut self,
+        command: ReplCommand,
+        out: &mut W,
+        write: &WriteArgs,
+    ) -> Result<bool> {
+        match command {
+            ReplCommand::List => {
+                for (index, chunk) in self.png.chunks().iter().enumerate() {
+                    writeln!(out, "{index}\t{}\t{} bytes", chunk.chunk_type(), chunk.length())?;
+                }
+            }
+            ReplCommand::Decode(chunk_type) => {
+                let chunk = self
+                    .png
+                    .chunks_of_type(&chunk_type)
+                    .next()
+                    .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+                let data = envelope::unwrap(chunk.data())?;
+                let message = render_message(&data, DecodeFormat::Utf8)?;
+                writeln!(out, "{}", String::from_utf8_lossy(&message))?;
+            }
+            ReplCommand::Remove(chunk_type) => {
+                self.png
+                    .remove_first_of_type(&chunk_type)
+                    .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+                self.unsaved = true;
+                writeln!(out, "Removed {chunk_type} chunk")?;
+            }
+            ReplCommand::Encode(chunk_type, message) => {
+                check_encode_type(&chunk_type, false, false)?;
+                self.png.insert_before_iend(Chunk::try_new(chunk_type.clone(), message.into_bytes())?);
+                self.unsaved = true;
+                writeln!(out, "Added {chunk_type} chunk")?;
+            }
+            ReplCommand::Save => {
+                write_png(&self.png, &self.path, write)?;
+                if !write.dry_run {
+                    self.unsaved = false;
+                    writeln!(out, "Saved {}", self.path.display())?;
+                }
+            }
+            ReplCommand::Quit { discard } => {
+                if self.unsaved && !discard {
+                    bail!("There are unsaved changes, save first or use quit! to discard them");
+                }
+                return Ok(false);
+            }
+            ReplCommand::Help => writeln!(out, "{HELP}")?,
+        }
+        Ok(true)
+    }
+
+    // a failing command is reported and the loop carries on, end of input counts as quit!
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, out: &mut W, write: &WriteArgs) -> Result<()> {
+        let mut lines = input.lines();
+        loop {
+            write!(out, "pngme> ")?;
+            out.flush()?;
+            let Some(line) = lines.next() else {
+                writeln!(out)?;
+                return Ok(());
+            };
+            let result = parse_line(&line?)
+                .and_then(|command| match command {
+                    Some(command) => self.execute(command, out, write),
+                    None => Ok(true),
+                });
+            match result {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(error) => writeln!(out, "error: {error:#}")?,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn chunk_type(chunk_type: &str) -> ChunkType {
+        ChunkType::from_str(chunk_type).unwrap()
+    }
+
+    fn testing_png() -> Png {
+        let chunks = vec![
+            Chunk::new(chunk_type("IHDR"), b"header".to_vec()),
+            Chunk::new(chunk_type("IEND"), Vec::new()),
+        ];
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_parse_line() {
+        assert_eq!(parse_line("").unwrap(), None);
+        assert_eq!(parse_line("   ").unwrap(), None);
+        assert_eq!(parse_line("list").unwrap(), Some(ReplCommand::List));
+        assert_eq!(
+            parse_line("decode ruSt").unwrap(),
+            Some(ReplCommand::Decode(chunk_type("ruSt")))
+        );
+        assert_eq!(
+            parse_line(r#"encode ruSt "hello \"there\" world""#).unwrap(),
+            Some(ReplCommand::Encode(chunk_type("ruSt"), r#"hello "there" world"#.to_string()))
+        );
+        assert_eq!(
+            parse_line(r#"encode ruSt """#).unwrap(),
+            Some(ReplCommand::Encode(chunk_type("ruSt"), String::new()))
+        );
+        assert_eq!(parse_line("quit!").unwrap(), Some(ReplCommand::Quit { discard: true }));
+
+        assert!(parse_line("decode").is_err());
+        assert!(parse_line("encode ruSt two words").is_err());
+        assert!(parse_line("decode ru!t").is_err());
+        assert!(parse_line("frobnicate").is_err());
+        assert!(parse_line(r#"encode ruSt "open"#).is_err());
+    }
+
+    #[test]
+    fn test_scripted_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        let png = testing_png();
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        let script = "\
+encode ruSt \"first message\"
+list
+decode ruSt
+quit
+remove ruSt
+remove ruSt
+encode ruSt second
+save
+quit
+list
+";
+        let mut repl = Repl::new(&path, png);
+        let mut out = Vec::new();
+        repl.run(script.as_bytes(), &mut out, &WriteArgs::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.contains("Added ruSt chunk"));
+        assert!(out.contains("1\truSt\t13 bytes"));
+        assert!(out.contains("first message"));
+        assert!(out.contains("error: There are unsaved changes"));
+        assert!(out.contains("error: Chunk not found: ruSt"));
+        // the second quit ends the session, so the last list never runs
+        assert_eq!(out.matches("IHDR").count(), 1);
+        assert!(!repl.has_unsaved_changes());
+
+        let saved = Png::from_file(&path).unwrap();
+        let message_type = chunk_type("ruSt");
+        let messages: Vec<_> = saved.chunks_of_type(&message_type).map(|c| c.data()).collect();
+        assert_eq!(messages, [b"second"]);
+    }
+
+    #[test]
+    fn test_nothing_is_written_without_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        let png = testing_png();
+        fs::write(&path, png.as_bytes()).unwrap();
+
+        let mut repl = Repl::new(&path, png);
+        let mut out = Vec::new();
+        repl.run("encode ruSt hi\nquit!\n".as_bytes(), &mut out, &WriteArgs::default()).unwrap();
+        assert!(repl.has_unsaved_changes());
+        assert_eq!(fs::read(&path).unwrap(), testing_png().as_bytes());
+    }
+}
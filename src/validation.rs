@@ -0,0 +1,196 @@
+use std::fmt::{Display, Formatter};
+
+use crate::chunk::{Chunk, CrcKind};
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+// everything Png::validate found, nothing is printed so callers decide what counts as fatal.
+// CRC mismatches only show up for a file parsed leniently, the strict parsers refuse them
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    pub crc_mismatches: Vec<CrcMismatch>,
+    pub ordering: Vec<OrderingViolation>,
+    pub duplicate_unique: Vec<(ChunkType, usize)>,
+    pub trailing: Option<TrailingData>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CrcMismatch {
+    pub index: usize,
+    pub chunk_type: ChunkType,
+    pub stored: u32,
+    pub calculated: u32,
+}
+
+// a missing IHDR or IEND is reported differently from one that's in the wrong place
+#[derive(Debug, PartialEq, Eq)]
+pub enum OrderingViolation {
+    MissingIhdr,
+    IhdrNotFirst { index: usize, first: ChunkType },
+    MissingIend,
+    IendNotLast { index: usize, chunks: usize, next: ChunkType },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct TrailingData {
+    pub length: usize,
+    pub offset: usize,
+}
+
+impl ValidationReport {
+    pub fn of(png: &Png) -> ValidationReport {
+        ValidationReport {
+            crc_mismatches: crc_mismatches(png.chunks()),
+            ordering: ordering_violations(png.chunks()),
+            duplicate_unique: png
+                .duplicate_unique_types()
+                .into_iter()
+                .map(|(chunk_type, count)| (chunk_type.clone(), count))
+                .collect(),
+            trailing: (!png.trailing_bytes().is_empty()).then(|| TrailingData {
+                length: png.trailing_bytes().len(),
+                offset: png.trailing_offset(),
+            }),
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        *self == ValidationReport::default()
+    }
+}
+
+fn crc_mismatches(chunks: &[Chunk]) -> Vec<CrcMismatch> {
+    chunks
+        .iter()
+        .enumerate()
+        .filter_map(|(index, chunk)| {
+            let calculated = CrcKind::default().checksum(&chunk.chunk_type().bytes(), chunk.data());
+            (calculated != chunk.crc()).then(|| CrcMismatch {
+                index,
+                chunk_type: chunk.chunk_type().clone(),
+                stored: chunk.crc(),
+                calculated,
+            })
+        })
+        .collect()
+}
+
+fn ordering_violations(chunks: &[Chunk]) -> Vec<OrderingViolation> {
+    let mut violations = Vec::new();
+    match chunks.iter().position(|chunk| chunk.chunk_type() == &ChunkType::IHDR) {
+        None => violations.push(OrderingViolation::MissingIhdr),
+        Some(0) => {}
+        Some(index) => violations.push(OrderingViolation::IhdrNotFirst {
+            index,
+            first: chunks[0].chunk_type().clone(),
+        }),
+    }
+    match chunks.iter().rposition(|chunk| chunk.chunk_type() == &ChunkType::IEND) {
+        None => violations.push(OrderingViolation::MissingIend),
+        Some(index) if index + 1 == chunks.len() => {}
+        Some(index) => violations.push(OrderingViolation::IendNotLast {
+            index,
+            chunks: chunks.len(),
+            next: chunks[index + 1].chunk_type().clone(),
+        }),
+    }
+    violations
+}
+
+impl Display for CrcMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Chunk {} ({}) has CRC {:08x}, its contents give {:08x}",
+            self.index + 1,
+            self.chunk_type,
+            self.stored,
+            self.calculated
+        )
+    }
+}
+
+// indices print 1 based, like print does
+impl Display for OrderingViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderingViolation::MissingIhdr => write!(f, "IHDR chunk is missing"),
+            OrderingViolation::IhdrNotFirst { index, first } => write!(
+                f,
+                "IHDR must be the first chunk but is chunk {}, after {first}",
+                index + 1
+            ),
+            OrderingViolation::MissingIend => write!(f, "IEND chunk is missing"),
+            OrderingViolation::IendNotLast { index, chunks, next } => write!(
+                f,
+                "IEND must be the last chunk but is chunk {} of {chunks}, followed by {next}",
+                index + 1
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.as_bytes().to_vec())
+    }
+
+    fn valid_chunks() -> Vec<Chunk> {
+        vec![chunk("IHDR", "header"), chunk("IDAT", "data"), chunk("IEND", "")]
+    }
+
+    #[test]
+    fn test_clean_file() {
+        let report = Png::from_chunks(valid_chunks()).validate();
+        assert!(report.is_clean(), "{report:?}");
+    }
+
+    #[test]
+    fn test_each_defect_is_reported() {
+        let bytes = Png::from_chunks(valid_chunks()).as_bytes();
+        // the IDAT CRC is the 4 bytes before IEND's 12, the border of the trailing junk
+        let mut damaged = bytes.clone();
+        let idat_crc = bytes.len() - 13;
+        damaged[idat_crc] ^= 0xff;
+        damaged.extend_from_slice(b"junk");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("damaged.png");
+        std::fs::write(&path, &damaged).unwrap();
+        let png = Png::from_file_lenient(&path).unwrap();
+
+        let report = png.validate();
+        assert_eq!(report.crc_mismatches.len(), 1);
+        assert_eq!(report.crc_mismatches[0].index, 1);
+        assert_eq!(report.crc_mismatches[0].chunk_type, ChunkType::IDAT);
+        assert_eq!(report.trailing, Some(TrailingData { length: 4, offset: bytes.len() }));
+        assert!(report.ordering.is_empty());
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn test_ordering_and_duplicates() {
+        let mut chunks = valid_chunks();
+        chunks.insert(0, chunk("tIME", "1234567"));
+        chunks.insert(1, chunk("tIME", "1234567"));
+        chunks.push(chunk("ruSt", "late"));
+        let report = Png::from_chunks(chunks).validate();
+
+        assert_eq!(report.duplicate_unique, [(ChunkType::from_str("tIME").unwrap(), 2)]);
+        let ordering: Vec<String> = report.ordering.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            ordering,
+            [
+                "IHDR must be the first chunk but is chunk 3, after tIME",
+                "IEND must be the last chunk but is chunk 5 of 6, followed by ruSt",
+            ]
+        );
+        assert!(report.crc_mismatches.is_empty() && report.trailing.is_none());
+
+        let report = Png::from_chunks(Vec::new()).validate();
+        assert_eq!(report.ordering, [OrderingViolation::MissingIhdr, OrderingViolation::MissingIend]);
+    }
+}
@@ -0,0 +1,182 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use notify::event::{AccessKind, EventKind};
+use notify::{RecursiveMode, Watcher};
+use sha2::{Digest, Sha256};
+
+// long enough to fold an editor's or image tool's multi step save into one change
+pub const DEBOUNCE: Duration = Duration::from_millis(300);
+
+// runs `operation` every time the file changes until interrupted. The parent directory is
+// watched rather than the file, since an atomic save replaces the file and a watch on the
+// old one would go quiet
+pub fn watch<F>(path: &Path, debounce: Duration, operation: F) -> Result<()>
+where
+    F: FnMut() -> Result<()>,
+{
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path.file_name().map(|name| name.to_os_string());
+
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) if is_change(&event.kind) => {
+                if event.paths.iter().any(|changed| changed.file_name() == file_name.as_deref()) {
+                    let _ = sender.send(());
+                }
+            }
+            Ok(_) => {}
+            Err(error) => warn!("Watch error: {error}"),
+        }
+    })
+    .context("Failed to start watching for changes")?;
+    watcher
+        .watch(directory, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", directory.display()))?;
+
+    info!("Watching {} for changes", path.display());
+    run_on_changes(path, fingerprint(path)?, &events, debounce, operation)
+}
+
+fn is_change(kind: &EventKind) -> bool {
+    match kind {
+        EventKind::Create(_) | EventKind::Modify(_) => true,
+        // a write finishing, some platforms report only this
+        EventKind::Access(AccessKind::Close(_)) => true,
+        _ => false,
+    }
+}
+
+// split from watch so the debouncing and self-write handling can be driven without a
+// real watcher. `written` is what the file looked like right after our own last write, a
+// change that leaves it like this is that write coming back around. Returns once the
+// event source goes away
+fn run_on_changes<F>(
+    path: &Path,
+    mut written: Option<[u8; 32]>,
+    events: &Receiver<()>,
+    debounce: Duration,
+    mut operation: F,
+) -> Result<()>
+where
+    F: FnMut() -> Result<()>,
+{
+    while wait_for_change(events, debounce) {
+        let current = fingerprint(path)?;
+        if current.is_none() {
+            debug!("{} is gone, waiting for it to come back", path.display());
+            continue;
+        }
+        if current == written {
+            debug!("Ignoring our own write to {}", path.display());
+            continue;
+        }
+        // keep watching after a failure, the next save may well be fine. Nothing was
+        // written then, so the same contents shouldn't trigger another attempt
+        match operation() {
+            Ok(()) => written = fingerprint(path)?,
+            Err(error) => {
+                warn!("{error:#}");
+                written = current;
+            }
+        }
+    }
+    Ok(())
+}
+
+// blocks until an event arrives and then until none has arrived for `debounce`, false once
+// the sender is gone and nothing is pending
+fn wait_for_change(events: &Receiver<()>, debounce: Duration) -> bool {
+    if events.recv().is_err() {
+        return false;
+    }
+    loop {
+        match events.recv_timeout(debounce) {
+            Ok(()) => continue,
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => return true,
+        }
+    }
+}
+
+// None while the file doesn't exist, e.g. between an unlink and a rename
+fn fingerprint(path: &Path) -> Result<Option<[u8; 32]>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(Sha256::digest(&bytes).into())),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_burst_runs_once_and_own_write_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        fs::write(&path, b"original").unwrap();
+        let written = fingerprint(&path).unwrap();
+
+        let (sender, events) = mpsc::channel();
+        // the file changes, reported as a burst of events like a real save
+        fs::write(&path, b"changed").unwrap();
+        for _ in 0..3 {
+            sender.send(()).unwrap();
+        }
+
+        let runs = Cell::new(0);
+        let mut echo = Some(sender);
+        run_on_changes(&path, written, &events, Duration::from_millis(10), || {
+            runs.set(runs.get() + 1);
+            fs::write(&path, format!("stamped {}", runs.get()))?;
+            // the watcher would report our own write too
+            if let Some(sender) = echo.take() {
+                sender.send(()).unwrap();
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(runs.get(), 1);
+        assert_eq!(fs::read(&path).unwrap(), b"stamped 1");
+    }
+
+    #[test]
+    fn test_failed_run_keeps_watching() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.png");
+        fs::write(&path, b"original").unwrap();
+        let written = fingerprint(&path).unwrap();
+
+        let (sender, events) = mpsc::channel();
+        fs::write(&path, b"changed").unwrap();
+        sender.send(()).unwrap();
+
+        let runs = Cell::new(0);
+        let mut sender = Some(sender);
+        run_on_changes(&path, written, &events, Duration::from_millis(10), || {
+            runs.set(runs.get() + 1);
+            if runs.get() == 1 {
+                // the next save comes in while the first attempt fails
+                fs::write(&path, b"changed again")?;
+                sender.take().unwrap().send(()).unwrap();
+                anyhow::bail!("not a png");
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(runs.get(), 2);
+    }
+}